@@ -17,16 +17,19 @@ use std::{
 use tokio::{sync::Barrier, task::spawn};
 use tracing::{debug, error, info, info_span, instrument, warn, Instrument};
 
-use crate::feed;
+use crate::feed::fetch::{FeedTls, Proxy, Secret};
+use crate::feed::{self, discover::MultipleFeeds, schedule};
 use crate::signal::{send_termination, wait_for_termination};
-use crate::update::{background_task, Command, EditArgs, COMMANDS};
+use crate::update::{background_task, AuthArgs, Command, EditArgs, COMMANDS};
 use crate::{discord, CONFIG};
 
 pub static GUILDS: OnceLock<Vec<GuildId>> = OnceLock::new();
 pub static USER_ID: OnceLock<UserId> = OnceLock::new();
 
 #[group]
-#[commands(ping, exit, add, remove, poll, edit, reload, export, import, useragent)]
+#[commands(
+    ping, exit, add, remove, poll, edit, reload, resume, status, auth, export, import, useragent
+)]
 pub struct Admin;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
@@ -226,8 +229,22 @@ pub async fn ping(ctx: &Context, msg: &Message) -> CommandResult {
     .await
 }
 
+// Reply to msg, or just send to its channel once it was deleted
+async fn respond(
+    ctx: &Context,
+    msg: &Message,
+    deleted: bool,
+    content: &str,
+) -> serenity::Result<Message> {
+    if deleted {
+        msg.channel_id.say(ctx, content).await
+    } else {
+        msg.reply(ctx, content).await
+    }
+}
+
 #[command]
-#[description("Add a feed to the bot list.")]
+#[description("Add a feed to the bot list, the URL can also be a web page linking to it.")]
 #[usage("~add <URL> [title]")]
 #[min_args(1)]
 #[max_args(2)]
@@ -261,26 +278,48 @@ pub async fn add(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult
 
         let title: Option<String> = args.single().ok();
 
-        let user_agent = CONFIG
-            .read()
-            .expect("failed to get CONFIG static")
-            .user_agent
-            .clone();
+        // Credentials in the url are taken as the feed's, don't leave them in
+        // the channel or repeat them
+        let public = feed::public_url(&url);
+        let deleted = public != url;
+        if deleted {
+            if let Err(e) = msg.delete(ctx).await {
+                warn!("Failed to delete message {}: {}", msg.id.0, e);
+            }
+        }
 
-        match feed::from_url(&url, title, None, user_agent).await {
+        match feed::from_url(&url, title, None, &Default::default()).await {
+            Err(e) if e.is::<MultipleFeeds>() => {
+                let multiple = e
+                    .downcast_ref::<MultipleFeeds>()
+                    .expect("error was checked to be MultipleFeeds");
+                let mut reply = format!(
+                    "{} links to {} feeds, use ~add with one of:",
+                    public,
+                    multiple.candidates.len()
+                );
+                for candidate in multiple.candidates.iter().take(10) {
+                    reply.push_str(&format!("\n- {}", candidate));
+                }
+                if let Err(err) = respond(ctx, msg, deleted, &reply).await {
+                    error!("Error replying to message {}: {}", msg.id.0, err);
+                    return Err(
+                        anyhow::anyhow!("error replying to message {}: {}", msg.id.0, err).into(),
+                    );
+                }
+                Ok(())
+            }
             Err(e) => {
-                match msg
-                    .reply(ctx, &format!("Failed to load feed from {}: {}", url, e))
-                    .await
-                {
+                let reply = format!("Failed to load feed from {}: {}", public, e);
+                match respond(ctx, msg, deleted, &reply).await {
                     Err(err) => {
                         error!(
                             "Failed loading feed from {}: {} and sending reply to {}: {}",
-                            url, e, msg.id.0, err
+                            public, e, msg.id.0, err
                         );
                         Err(anyhow::anyhow!(
                             "Failed loading feed from {}: {} and sending reply to {}: {}",
-                            url,
+                            public,
                             e,
                             msg.id.0,
                             err
@@ -288,12 +327,32 @@ pub async fn add(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult
                         .into())
                     }
                     _ => {
-                        error!("Failed loading feed from {}: {}", url, e);
-                        Err(anyhow::anyhow!("Failed loading feed from {}: {}", url, e).into())
+                        error!("Failed loading feed from {}: {}", public, e);
+                        Err(anyhow::anyhow!("Failed loading feed from {}: {}", public, e).into())
                     }
                 }
             }
-            Ok(feed) => {
+            Ok(mut feed) => {
+                // Just fetched, so not due again until its interval passed
+                feed.set_last_updated(chrono::offset::Utc::now());
+                if !feed.diagnostics().is_empty() {
+                    let mut reply = format!(
+                        "Some items of {} could not be read as they are:",
+                        feed.title()
+                    );
+                    for diagnostic in feed.diagnostics().iter().take(10) {
+                        // Keeps the reply under Discord's 2000 character limit
+                        let line = diagnostic.to_string().chars().take(180).collect::<String>();
+                        reply.push_str(&format!("\n- {}", line));
+                    }
+                    if feed.diagnostics().len() > 10 {
+                        reply.push_str(&format!("\n- and {} more", feed.diagnostics().len() - 10));
+                    }
+                    if let Err(e) = respond(ctx, msg, deleted, &reply).await {
+                        error!("Error replying to message {}: {}", msg.id.0, e);
+                    }
+                }
+
                 let barrier = Arc::new(Barrier::new(2));
                 let send = COMMANDS.get().expect("failed to get COMMANDS static");
                 if let Err(e) = send
@@ -402,6 +461,21 @@ fn parse_edit_args(raw_args: &[String]) -> anyhow::Result<EditArgs> {
             "title" => args.title = Some(parts[1].to_string()),
             "category" => args.category = Some(parts[1].to_string()),
             "url" | "link" => args.url = Some(parts[1].to_string()),
+            "interval" => args.interval = Some(schedule::parse_interval(parts[1])?),
+            "mininterval" | "min_interval" => {
+                args.min_interval = Some(schedule::parse_interval(parts[1])?)
+            }
+            "maxinterval" | "max_interval" => {
+                args.max_interval = Some(schedule::parse_interval(parts[1])?)
+            }
+            "adaptive" => {
+                args.adaptive = Some(match parts[1].to_lowercase().as_ref() {
+                    "on" | "true" | "yes" => Some(true),
+                    "off" | "false" | "no" => Some(false),
+                    "none" | "default" => None,
+                    value => anyhow::bail!("adaptive must be on, off or default, not {}", value),
+                })
+            }
             _ => warn!("Encountered unknown KEY for edit command: {}.", parts[0]),
         }
     }
@@ -410,7 +484,9 @@ fn parse_edit_args(raw_args: &[String]) -> anyhow::Result<EditArgs> {
 }
 
 #[command]
-#[description("Edit feed. Keys are url, title, and category.")]
+#[description(
+    "Edit feed. Keys are url, title, category, interval, mininterval and maxinterval (seconds or a number with s, m, h or d, none for the default), and adaptive (on, off or default)."
+)]
 #[usage("~edit <feed> <KEY=VALUE>...")]
 #[min_args(2)]
 pub async fn edit(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
@@ -611,6 +687,275 @@ pub async fn reload(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
     .await
 }
 
+#[command]
+#[description("Show how often feeds are polled and when they are next.")]
+#[usage("~status [url|title]")]
+#[max_args(1)]
+pub async fn status(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    (async {
+        let id = if args.is_empty() {
+            None
+        } else {
+            match args.parse::<String>() {
+                Err(e) => match msg.reply(ctx, "Failed to parse argument.").await {
+                    Err(err) => {
+                        error!(
+                            "Error parsing status argument: {} and replying to message {}: {}",
+                            e, msg.id.0, err
+                        );
+                        return Err(anyhow::anyhow!(
+                            "Error parsing status argument: {} and replying to message {}: {}",
+                            e,
+                            msg.id.0,
+                            err
+                        )
+                        .into());
+                    }
+                    Ok(_) => {
+                        error!("Error parsing status argument: {}.", e);
+                        return Err(anyhow::anyhow!("Error parsing status argument: {}.", e).into());
+                    }
+                },
+                Ok(s) => Some(s),
+            }
+        };
+
+        let barrier = Arc::new(Barrier::new(2));
+        let send = COMMANDS
+            .get()
+            .expect("failed to read COMMANDS static")
+            .clone();
+        if let Err(e) = send
+            .send((Command::Status(msg.clone(), id), barrier.clone()))
+            .await
+        {
+            error!("Failed to send on COMMANDS channel: {}", e);
+            match msg.reply(ctx, "Internal error").await {
+                Err(err) => {
+                    error!(
+                        "Failed to send command: {} and reply to message {}: {}",
+                        e, msg.id.0, err
+                    );
+                    return Err(anyhow::anyhow!(
+                        "Failed to send command: {} and reply to message {}: {}",
+                        e,
+                        msg.id.0,
+                        err
+                    )
+                    .into());
+                }
+                Ok(_) => {
+                    error!("Failed to send command: {}", e);
+                    return Err(anyhow::anyhow!("Failed to send command: {}", e).into());
+                }
+            }
+        }
+
+        barrier.wait().await;
+        Ok(())
+    })
+    .instrument(info_span!("~status"))
+    .await
+}
+
+#[command]
+#[description("Resume a feed that was paused after failing to update.")]
+#[usage("~resume <url|title>")]
+#[num_args(1)]
+pub async fn resume(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    (async {
+        let id = match args.parse::<String>() {
+            Err(e) => match msg.reply(ctx, "Failed to parse argument.").await {
+                Err(err) => {
+                    error!(
+                        "Error parsing resume argument: {} and replying to message {}: {}",
+                        e, msg.id.0, err
+                    );
+                    return Err(anyhow::anyhow!(
+                        "Error parsing resume argument: {} and replying to message {}: {}",
+                        e,
+                        msg.id.0,
+                        err
+                    )
+                    .into());
+                }
+                Ok(_) => {
+                    error!("Error parsing resume argument: {}.", e);
+                    return Err(anyhow::anyhow!("Error parsing resume argument: {}.", e).into());
+                }
+            },
+            Ok(s) => s,
+        };
+
+        let barrier = Arc::new(Barrier::new(2));
+        let send = COMMANDS
+            .get()
+            .expect("failed to read COMMANDS static")
+            .clone();
+        if let Err(e) = send
+            .send((Command::ResumeFeed(msg.clone(), id), barrier.clone()))
+            .await
+        {
+            error!("Failed to send on COMMANDS channel: {}", e);
+            match msg.reply(ctx, "Internal error").await {
+                Err(err) => {
+                    error!(
+                        "Failed to send command: {} and reply to message {}: {}",
+                        e, msg.id.0, err
+                    );
+                    return Err(anyhow::anyhow!(
+                        "Failed to send command: {} and reply to message {}: {}",
+                        e,
+                        msg.id.0,
+                        err
+                    )
+                    .into());
+                }
+                Ok(_) => {
+                    error!("Failed to send command: {}", e);
+                    return Err(anyhow::anyhow!("Failed to send command: {}", e).into());
+                }
+            }
+        }
+
+        barrier.wait().await;
+        Ok(())
+    })
+    .instrument(info_span!("~resume"))
+    .await
+}
+
+#[instrument(level = "trace", skip(raw_args))]
+fn parse_auth_args(raw_args: &[String]) -> anyhow::Result<AuthArgs> {
+    let kind = raw_args.first().map(|kind| kind.to_lowercase());
+    let args = match (kind.as_deref(), &raw_args[1.min(raw_args.len())..]) {
+        (Some("basic"), [username]) => AuthArgs::Basic(username.clone(), None),
+        (Some("basic"), [username, password]) => {
+            AuthArgs::Basic(username.clone(), Some(Secret(password.clone())))
+        }
+        (Some("bearer"), [token]) => AuthArgs::Bearer(Secret(token.clone())),
+        (Some("header"), [name]) => AuthArgs::Header(name.clone(), None),
+        (Some("header"), [name, value]) => {
+            reqwest::header::HeaderValue::from_str(value)
+                .map_err(|_| anyhow::anyhow!("Header value is not valid."))?;
+            AuthArgs::Header(name.clone(), Some(Secret(value.clone())))
+        }
+        (Some("useragent"), []) => AuthArgs::UserAgent(None),
+        (Some("useragent"), [user_agent]) => AuthArgs::UserAgent(Some(user_agent.clone())),
+        (Some("proxy"), []) => AuthArgs::Proxy(None),
+        (Some("proxy"), [url, no_proxy @ ..]) if no_proxy.len() <= 1 => {
+            let proxy = Proxy {
+                url: url.clone(),
+                no_proxy: no_proxy
+                    .iter()
+                    .flat_map(|hosts| hosts.split(','))
+                    .map(|host| host.trim().to_owned())
+                    .filter(|host| !host.is_empty())
+                    .collect(),
+            };
+            proxy
+                .to_reqwest()
+                .map_err(|_| anyhow::anyhow!("Proxy url is not valid."))?;
+            AuthArgs::Proxy(Some(proxy))
+        }
+        (Some("tls"), [mode]) if mode == "verify" => AuthArgs::Tls(Default::default()),
+        (Some("tls"), [mode]) if mode == "insecure" => AuthArgs::Tls(FeedTls {
+            insecure: true,
+            ..Default::default()
+        }),
+        (Some("tls"), [mode, path]) if mode == "pin" => {
+            let tls = FeedTls {
+                pinned_certificate: Some(path.into()),
+                ..Default::default()
+            };
+            tls.validate()?;
+            AuthArgs::Tls(tls)
+        }
+        (Some("timeout"), []) => AuthArgs::Timeout(None),
+        (Some("timeout"), [seconds]) => match seconds.parse() {
+            Ok(0) | Err(_) => anyhow::bail!("Timeout must be a positive number of seconds."),
+            Ok(seconds) => AuthArgs::Timeout(Some(seconds)),
+        },
+        (Some("clear"), []) => AuthArgs::Clear,
+        _ => anyhow::bail!("Unknown or incomplete request option."),
+    };
+
+    if let AuthArgs::Header(ref name, _) = args {
+        reqwest::header::HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| anyhow::anyhow!("Header name is not valid."))?;
+    }
+    Ok(args)
+}
+
+#[command]
+#[description(
+    "Set the credentials, headers, user agent, proxy, certificate checks or timeout used when fetching a feed. The message is deleted since it may hold a secret."
+)]
+#[usage(
+    "~auth <url|title> <basic USER [PASSWORD] | bearer TOKEN | header NAME [VALUE] | useragent [AGENT] | proxy [URL [NO,PROXY,HOSTS]] | tls <verify | insecure | pin PEM_FILE> | timeout [SECONDS] | clear>"
+)]
+pub async fn auth(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    (async {
+        // The command may hold a secret, don't leave it in the channel
+        if let Err(e) = msg.delete(ctx).await {
+            warn!("Failed to delete message {}: {}", msg.id.0, e);
+        }
+
+        let raw_args = match args
+            .raw_quoted()
+            .map(|arg| arg.parse::<String>())
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Err(e) => return Err(anyhow::anyhow!("Failed to parse auth arguments: {}", e).into()),
+            Ok(args) => args,
+        };
+        // Counted here rather than with min_args and max_args, so that the
+        // message is deleted whatever is wrong with it
+        let auth_args = match raw_args.split_first() {
+            Some((_, options)) if !options.is_empty() => parse_auth_args(options),
+            _ => Err(anyhow::anyhow!("Expected a feed and a request option.")),
+        };
+        let auth_args = match auth_args {
+            // The message is gone, so this can't be a reply
+            Err(e) => {
+                if let Err(err) = msg.channel_id.say(ctx, &e.to_string()).await {
+                    error!("Failed to send message to {}: {}", msg.channel_id.0, err);
+                }
+                return Err(e.into());
+            }
+            Ok(args) => args,
+        };
+
+        // Keeps the secret out of the logged command
+        let mut msg = msg.clone();
+        msg.content.clear();
+
+        let barrier = Arc::new(Barrier::new(2));
+        let send = COMMANDS
+            .get()
+            .expect("failed to read COMMANDS static")
+            .clone();
+        if let Err(e) = send
+            .send((
+                Command::SetAuth(msg.clone(), raw_args[0].clone(), auth_args),
+                barrier.clone(),
+            ))
+            .await
+        {
+            error!("Failed to send on COMMANDS channel: {}", e);
+            if let Err(err) = msg.channel_id.say(ctx, "Internal error").await {
+                error!("Failed to send message to {}: {}", msg.channel_id.0, err);
+            }
+            return Err(anyhow::anyhow!("Failed to send command: {}", e).into());
+        }
+
+        barrier.wait().await;
+        Ok(())
+    })
+    .instrument(info_span!("~auth"))
+    .await
+}
+
 #[command]
 #[description("Import OPML feed list")]
 #[usage("~import <opml file attached to message>")]
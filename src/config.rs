@@ -8,6 +8,12 @@ use std::{
 };
 use tracing::{debug, error, info, instrument};
 
+use crate::feed::{
+    fetch::{Proxy, TlsConfig},
+    limit::RateLimitConfig,
+    websub::WebSubConfig,
+};
+
 #[derive(Clone, PartialEq, Eq, Hash, Parser)]
 #[command(name = "rsspal")]
 #[command(version = env!("CARGO_PKG_VERSION"))]
@@ -47,8 +53,45 @@ pub struct Config {
     pub interval: u64,
     // User agent to use with requests
     pub user_agent: Option<String>,
+    // Pause a feed after this many failed polls in a row, 0 to never pause
+    #[serde(default = "default_max_failures")]
+    pub max_failures: u32,
+    // Poll feeds as often as they post, between minInterval and maxInterval
+    // seconds, unless a feed sets otherwise
+    #[serde(default)]
+    pub adaptive: bool,
+    #[serde(default = "default_min_interval")]
+    pub min_interval: u64,
+    #[serde(default = "default_max_interval")]
+    pub max_interval: u64,
+    // Concurrent fetches and requests per host
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
+    // Proxy for every feed that doesn't set its own
+    #[serde(default)]
+    pub proxy: Option<Proxy>,
+    // Root certificates and client certificate for every feed
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    // Callback server for hubs to push updates to, feeds are only polled
+    // without it
+    #[serde(default)]
+    pub websub: Option<WebSubConfig>,
+}
+
+fn default_max_failures() -> u32 {
+    10
+}
+
+fn default_min_interval() -> u64 {
+    5 * 60
 }
 
+fn default_max_interval() -> u64 {
+    24 * 60 * 60
+}
+
+#[cfg_attr(test, allow(dead_code))]
 fn get_token() -> anyhow::Result<String> {
     let token = env::var("DISCORD_TOKEN")?;
     Ok(token)
@@ -87,7 +130,29 @@ pub fn get_config_path() -> PathBuf {
     cwd.join("config.toml")
 }
 
+// Used without a config file
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            config_file: get_config_path(),
+            data_dir: get_data_dir(),
+            discord_token: String::default(),
+            interval: 600,
+            user_agent: None,
+            max_failures: default_max_failures(),
+            adaptive: false,
+            min_interval: default_min_interval(),
+            max_interval: default_max_interval(),
+            rate_limit: RateLimitConfig::default(),
+            proxy: None,
+            tls: None,
+            websub: None,
+        }
+    }
+}
+
 impl Config {
+    #[cfg_attr(test, allow(dead_code))]
     pub fn new() -> anyhow::Result<Self> {
         let args = Args::parse();
 
@@ -112,10 +177,7 @@ impl Config {
         } else {
             Config {
                 config_file: config_path.clone(),
-                data_dir: get_data_dir(),
-                discord_token: String::default(),
-                interval: 600,
-                user_agent: None,
+                ..Default::default()
             }
         };
 
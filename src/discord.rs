@@ -8,6 +8,7 @@ use tracing::{debug, error, info, instrument, warn};
 
 use crate::admin_commands::GUILDS;
 use crate::feed::atom::Entry;
+use crate::feed::json::Item as JsonItem;
 use crate::feed::rss::RssItem;
 use crate::feed::Feed;
 
@@ -255,12 +256,55 @@ pub async fn publish_atom_entry(
 ) -> anyhow::Result<()> {
     info!("Publishing item {} to feed {}", entry.title, feed_name);
     debug!("{:?}", entry);
-    let channel_name = if entry.read.is_none() {
-        title_to_channel_name(feed_name)
-    } else {
-        format!("read-{}", &title_to_channel_name(feed_name))
-    };
+    publish_embed(
+        feed_name,
+        &entry.title,
+        entry.read.is_some(),
+        entry.to_embed(),
+        ctx,
+    )
+    .await
+}
+
+#[instrument(skip(ctx))]
+pub async fn publish_rss_item(
+    feed_name: &str,
+    item: &RssItem,
+    ctx: &Context,
+) -> anyhow::Result<()> {
+    info!("Publishing item {} to feed {}", item.link, feed_name);
+    publish_embed(
+        feed_name,
+        &item.link,
+        item.read.is_some(),
+        item.to_embed(),
+        ctx,
+    )
+    .await
+}
+
+#[instrument(skip(ctx))]
+pub async fn publish_json_item(
+    feed_name: &str,
+    item: &JsonItem,
+    ctx: &Context,
+) -> anyhow::Result<()> {
+    info!("Publishing item {} to feed {}", item.id, feed_name);
+    debug!("{:?}", item);
+    publish_embed(
+        feed_name,
+        item.get_link_href(),
+        item.read.is_some(),
+        item.to_embed(),
+        ctx,
+    )
+    .await
+}
 
+// Send a plain message about the feed to its channel in every guild
+#[instrument(skip(ctx))]
+pub async fn publish_notice(feed_name: &str, notice: &str, ctx: &Context) -> anyhow::Result<()> {
+    let channel_name = title_to_channel_name(feed_name);
     let guilds = {
         if let Some(g) = GUILDS.get() {
             g.clone()
@@ -270,53 +314,29 @@ pub async fn publish_atom_entry(
         }
     };
 
-    let embed_cb = entry.to_embed();
-
     for guild in guilds {
-        let mut channels = guild.channels(ctx).await?;
-        match guild.get_active_threads(ctx).await {
-            Err(e) => warn!("Failed to get active threads in {}: {}", guild.0, e),
-            Ok(threads) => {
-                channels.extend(threads.threads.into_iter().map(|c| (c.id, c)));
-            }
-        }
-        let to_publish = channels.iter().find_map(|(_, c)| {
-            if c.name == channel_name {
-                Some(c)
-            } else {
-                None
-            }
-        });
-
-        if let Some(channel) = to_publish {
-            info!(
-                "Publishing item {}, feed {}, on guild {}, channel {}.",
-                entry.title, feed_name, guild.0, channel.id.0
-            );
-            let msg = channel
-                .send_message(ctx, |msg| msg.embed(&embed_cb))
-                .await?;
-            let emoji = if entry.read.is_some() { '📕' } else { '📖' };
-            if let Err(e) = msg.react(ctx, emoji).await {
-                warn!("Unable to react to message for {}: {}", entry.title, e);
-            }
+        let channels = guild.channels(ctx).await?;
+        if let Some(channel) = channels.values().find(|c| c.name == channel_name) {
+            channel.say(ctx, notice).await?;
         }
     }
 
     Ok(())
 }
 
-#[instrument(skip(ctx))]
-pub async fn publish_rss_item(
+// Send an item embed to the feed channel (or read thread) in every guild
+#[instrument(skip(embed_cb, ctx), level = "debug")]
+async fn publish_embed(
     feed_name: &str,
-    item: &RssItem,
+    item_name: &str,
+    read: bool,
+    embed_cb: impl Fn(&mut CreateEmbed) -> &mut CreateEmbed,
     ctx: &Context,
 ) -> anyhow::Result<()> {
-    info!("Publishing item {} to feed {}", item.link, feed_name);
-    let channel_name = if item.read.is_none() {
-        title_to_channel_name(feed_name)
-    } else {
+    let channel_name = if read {
         format!("read-{}", &title_to_channel_name(feed_name))
+    } else {
+        title_to_channel_name(feed_name)
     };
 
     let guilds = {
@@ -328,8 +348,6 @@ pub async fn publish_rss_item(
         }
     };
 
-    let embed_cb = item.to_embed();
-
     for guild in guilds {
         let mut channels = guild.channels(ctx).await?;
         match guild.get_active_threads(ctx).await {
@@ -345,17 +363,18 @@ pub async fn publish_rss_item(
                 None
             }
         });
+
         if let Some(channel) = to_publish {
             info!(
                 "Publishing item {}, feed {}, channel {}, on guild {}.",
-                item.link, feed_name, channel.name, guild.0
+                item_name, feed_name, channel.name, guild.0
             );
             let msg = channel
                 .send_message(ctx, |msg| msg.embed(&embed_cb))
                 .await?;
-            let emoji = if item.read.is_some() { '📕' } else { '📖' };
+            let emoji = if read { '📕' } else { '📖' };
             if let Err(e) = msg.react(ctx, emoji).await {
-                warn!("Unable to react to message for {}: {}", item.link, e);
+                warn!("Unable to react to message for {}: {}", item_name, e);
             }
         }
     }
@@ -409,7 +428,7 @@ pub async fn setup_channels(feeds: &[Feed], ctx: &Context) {
         let mut channels: HashMap<_, _> = channels.into_iter().map(|c| (c.id, c)).collect();
 
         for feed in feeds {
-            let chan_name = title_to_channel_name(&feed.title());
+            let chan_name = title_to_channel_name(feed.title());
             if setup_channel_category(
                 guild.0,
                 feed.discord_category(),
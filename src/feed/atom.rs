@@ -1,18 +1,23 @@
-use chrono::{DateTime, Datelike, Duration, Timelike, Utc};
-use quick_xml::de::from_reader;
-use reqwest::{self, Url};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serenity::builder::CreateEmbed;
-use std::{
-    fs::File,
-    io::{BufRead, BufReader},
-};
-use tracing::{debug, info_span, instrument, Instrument};
+use std::io::BufRead;
+use tracing::{debug, instrument};
+
+use super::fetch::{RequestOptions, Validators};
+use super::health::Health;
+use super::html;
+use super::lenient::{self, Diagnostic};
+use super::media::{MediaContent, MediaGroup, Thumbnail};
+use super::schedule::Schedule;
+use super::text::{self, Text, TextType};
+use super::websub::Subscription;
 
 // Atom Feed file
 #[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq, Default)]
 pub struct AtomFeed {
     pub id: String,
+    #[serde(deserialize_with = "text::plain")]
     pub title: String,
     pub updated: Option<DateTime<Utc>>,
     pub author: Option<Author>,
@@ -22,20 +27,46 @@ pub struct AtomFeed {
     pub category: Vec<Category>,
     pub icon: Option<String>,
     pub logo: Option<String>,
+    #[serde(deserialize_with = "text::plain_opt", default)]
     pub rights: Option<String>,
+    #[serde(deserialize_with = "text::plain_opt", default)]
     pub subtitle: Option<String>,
     #[serde(default)]
     pub entry: Vec<Entry>,
+    #[serde(deserialize_with = "super::rss::deserialize_ttl", default)]
     pub ttl: Option<usize>,
-    #[serde(default)]
-    pub skip_days: Vec<super::rss::Day>,
-    #[serde(default)]
-    pub skip_hours: Vec<super::rss::Hour>,
+    // RSS syndication module, see Feed::syndication
+    #[serde(rename = "sy-updatePeriod", default)]
+    pub sy_update_period: Option<String>,
+    #[serde(rename = "sy-updateFrequency", default)]
+    pub sy_update_frequency: Option<String>,
+    #[serde(rename = "sy-updateBase", default)]
+    pub sy_update_base: Option<String>,
+    // Honored like in RSS, though only found in Atom feeds converted from it
+    #[serde(rename = "skipDays", default)]
+    pub skip_days: super::rss::SkipDays,
+    #[serde(rename = "skipHours", default)]
+    pub skip_hours: super::rss::SkipHours,
     #[serde(default)]
     pub last_updated: Option<DateTime<Utc>>,
     #[serde(default)]
     pub url: String,
     pub discord_category: Option<String>,
+    // Entries skipped or repaired the last time the feed was parsed
+    #[serde(default)]
+    pub diagnostics: Vec<Diagnostic>,
+    // ETag and Last-Modified of the last full response, for conditional requests
+    #[serde(default)]
+    pub validators: Validators,
+    #[serde(default)]
+    pub health: Health,
+    // Credentials and headers for the feed's requests
+    #[serde(default)]
+    pub request: RequestOptions,
+    #[serde(default)]
+    pub websub: Option<Subscription>,
+    #[serde(default)]
+    pub schedule: Schedule,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq, Default)]
@@ -64,6 +95,7 @@ pub struct Category {
 pub struct Entry {
     #[serde(with = "entry_id")]
     pub id: String,
+    #[serde(deserialize_with = "text::plain")]
     pub title: String,
     #[serde(default)]
     pub link: Vec<Link>,
@@ -71,12 +103,59 @@ pub struct Entry {
     pub author: Option<Author>,
     pub contributer: Option<Contributer>,
     pub published: Option<DateTime<Utc>>,
+    #[serde(deserialize_with = "text::plain_opt", default)]
     pub rights: Option<String>,
     pub source: Option<Source>,
-    pub summary: Option<String>,
+    pub summary: Option<Text>,
+    pub content: Option<Content>,
     pub read: Option<()>,
     pub enclosure: Option<super::rss::Enclosure>,
     pub comments: Option<String>,
+    #[serde(rename = "media-group")]
+    pub media: Option<MediaGroup>,
+    // media:content, media:thumbnail and media:description outside of a
+    // group, folded into media when parsing
+    #[serde(rename = "media-content", default, skip_serializing)]
+    pub media_content: Vec<MediaContent>,
+    #[serde(rename = "media-thumbnail", default, skip_serializing)]
+    pub media_thumbnail: Vec<Thumbnail>,
+    #[serde(rename = "media-description", default, skip_serializing)]
+    pub media_description: Option<Text>,
+}
+
+// Entry content (RFC 4287 section 4.1.3), either inline or linked with src
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq, Default)]
+pub struct Content {
+    #[serde(rename = "@type")]
+    pub content_type: Option<String>,
+    #[serde(rename = "@src")]
+    pub src: Option<String>,
+    #[serde(rename = "$value", default)]
+    pub value: String,
+}
+
+impl Content {
+    // Inline content as a text construct, if it is textual
+    pub fn to_text(&self) -> Option<Text> {
+        if self.src.is_some() {
+            return None;
+        }
+
+        let kind = match self.content_type.as_deref().map(str::trim) {
+            None | Some("text") => TextType::Text,
+            Some("html") | Some("text/html") => TextType::Html,
+            Some("xhtml") => TextType::Xhtml,
+            Some(t) if text::is_xml_mime_type(t) => TextType::Xhtml,
+            Some(t) if t.starts_with("text/") => TextType::Text,
+            // Anything else is base64 encoded binary content
+            _ => return None,
+        };
+
+        Some(Text {
+            kind,
+            value: self.value.clone(),
+        })
+    }
 }
 
 mod entry_id {
@@ -127,25 +206,50 @@ impl Entry {
 
         if let Some(ref enc) = self.enclosure {
             if super::is_image_mime_type(&enc.content_type) {
-                Some(&enc.url)
-            } else {
-                None
+                return Some(&enc.url);
             }
-        } else {
-            None
         }
+
+        self.media.as_ref().and_then(MediaGroup::thumbnail)
+    }
+
+    fn apply_media(&mut self) {
+        self.media = MediaGroup::merge(
+            self.media.take(),
+            std::mem::take(&mut self.media_content),
+            std::mem::take(&mut self.media_thumbnail),
+            self.media_description.take(),
+        );
     }
 
     #[instrument(level = "debug")]
     pub fn to_embed(&self) -> impl Fn(&mut CreateEmbed) -> &mut CreateEmbed {
         let author = self.author.clone();
-        let description = self.summary.clone();
+        let link = self.get_link_href().to_owned();
+        let content = self.content.as_ref().and_then(Content::to_text);
+        let markdown = self
+            .summary
+            .as_ref()
+            .or(content.as_ref())
+            .or(self.media.as_ref().and_then(MediaGroup::description))
+            .map(|t| t.to_markdown(Some(&link)))
+            .unwrap_or_default();
+        // Inline images in the full content are fair game even when showing the summary
+        let inline_img = markdown.image.or_else(|| {
+            content
+                .as_ref()
+                .and_then(|c| c.to_markdown(Some(&link)).image)
+        });
+        let description = Some(markdown.text)
+            .filter(|d| !d.is_empty())
+            .map(html::limit_description);
+        let content_src = self.content.as_ref().and_then(|c| c.src.clone());
         let title = self.title.clone();
-        let enclosure = self.get_enclosure_img().map(String::from);
+        let enclosure = self.get_enclosure_img().map(String::from).or(inline_img);
         let date = self.published;
-        let link = self.get_link_href().to_owned();
         let comments = self.comments.clone();
         let source = self.source.clone();
+        let media = self.media.clone();
 
         move |embed: &mut CreateEmbed| {
             if let Some(ref a) = author {
@@ -154,7 +258,7 @@ impl Entry {
                         author.url(uri);
                     };
                     if let Some(ref email) = a.email {
-                        author.name(&format!("{} ({})", a.name, email))
+                        author.name(format!("{} ({})", a.name, email))
                     } else {
                         author.name(&a.name)
                     }
@@ -178,6 +282,10 @@ impl Entry {
             }
             embed.field("link", &link, false);
 
+            if let Some(ref src) = content_src {
+                embed.field("content", src, false);
+            }
+
             if let Some(ref c) = comments {
                 embed.field("comments", c, true);
             }
@@ -188,6 +296,10 @@ impl Entry {
                 embed.field("source updated", s.updated, true);
             }
 
+            if let Some(ref m) = media {
+                m.add_fields(embed);
+            }
+
             embed
         }
     }
@@ -213,79 +325,52 @@ pub struct Source {
     updated: DateTime<Utc>,
 }
 
-pub fn xml_from_reader(url: impl Into<String>, read: impl BufRead) -> anyhow::Result<AtomFeed> {
-    from_reader(read).map_err(|e| anyhow::anyhow!("{}: {}", url.into(), e))
+// The document must already be UTF-8, see charset::to_utf8
+pub fn xml_from_reader(url: impl Into<String>, mut read: impl BufRead) -> anyhow::Result<AtomFeed> {
+    let url = url.into();
+    let mut bytes = Vec::new();
+    read.read_to_end(&mut bytes)?;
+
+    // Malformed documents are left for the deserializer to report
+    let bytes = super::doctype::strip(&bytes).unwrap_or(bytes);
+    let bytes = super::namespace::qualify(&bytes, &[]).unwrap_or(bytes);
+    let bytes = text::normalize_xhtml(&bytes).unwrap_or(bytes);
+    let (mut feed, diagnostics) = lenient::from_xml::<AtomFeed>(&bytes, "entry")
+        .map_err(|e| anyhow::anyhow!("{}: {}", url, e))?;
+    feed.diagnostics = diagnostics;
+
+    feed.entry.iter_mut().for_each(Entry::apply_media);
+    Ok(feed)
 }
 
-impl AtomFeed {
-    // Create a feed item from a URL to an RSS feed,
-    // Filling the title and category fields if given
-    // (title defaults to title from rss feed)
-    #[instrument(level = "debug", skip(url, user_agent))]
-    pub async fn from_url(
-        url: impl AsRef<str>,
-        user_agent: Option<impl AsRef<str>>,
-    ) -> anyhow::Result<Self> {
-        let url: Url = Url::parse(url.as_ref())?;
-        let feed_url = url.to_string();
-
-        // Stream rss feed to the write end of the pipe in a new task
-        let mut feed = if url.scheme() == "http" || url.scheme() == "https" {
-            let bytes = (async {
-                let client = if let Some(user) = user_agent {
-                    reqwest::ClientBuilder::new()
-                        .user_agent(user.as_ref())
-                        .build()?
-                } else {
-                    reqwest::ClientBuilder::new().build()?
-                };
-                let req = client.get(url.clone()).build()?;
-                client.execute(req).await?.bytes().await
-            })
-            .instrument(info_span!("AtomFeed::reqwest"))
-            .await?;
-            xml_from_reader(url, BufReader::new(bytes.as_ref()))
-        } else if url.scheme() == "file" {
-            let _ = info_span!("AtomFeed::File");
-            xml_from_reader(&feed_url, BufReader::new(File::open(url.path())?))
-        } else {
-            anyhow::bail!("{}: unsupported url schema", url)
-        }?;
+impl lenient::Document for AtomFeed {
+    type Item = Entry;
+
+    fn items(&mut self) -> &mut Vec<Entry> {
+        &mut self.entry
+    }
+}
 
-        feed.url = feed_url;
-        Ok(feed)
+impl lenient::Item for Entry {
+    fn title(&self) -> Option<String> {
+        Some(self.title.clone())
     }
+}
 
-    // Use metadata on channel to figure out if it's time to update
+impl AtomFeed {
+    // Whether the feed asks to be skipped right now, the ttl and sy: update
+    // period are part of the feed's interval (see Feed::next_poll)
     #[instrument(level = "trace")]
-    pub fn should_update(&self) -> bool {
-        if let Some(last_update) = self.last_updated {
-            let now = chrono::offset::Utc::now();
-            if self.skip_days.contains(&now.date_naive().weekday().into()) {
-                debug!("Feed {} should be skipped today.", self.title);
-                return false;
-            }
-            if self.skip_hours.contains(&now.time().hour().into()) {
-                debug!("Feed {} should be skipped this hour.", self.title);
-                return false;
-            }
+    pub fn should_update(&self, now: DateTime<Utc>) -> bool {
+        if self.last_updated.is_none() {
+            return true;
+        }
 
-            if let Some(ttl) = self.ttl {
-                debug!(
-                    "Checking ttl to see if {} should be updated now.",
-                    self.title
-                );
-                let duration_since = now.signed_duration_since(last_update);
-                match Duration::from_std(std::time::Duration::from_secs(ttl as u64 * 60)) {
-                    Ok(ttl) => duration_since >= ttl,
-                    _ => true,
-                }
-            } else {
-                true
-            }
-        } else {
-            true
+        if super::rss::is_skipped(&self.skip_hours, &self.skip_days, now) {
+            debug!("Feed {} should be skipped now.", self.title);
+            return false;
         }
+        true
     }
 }
 
@@ -295,7 +380,11 @@ mod test {
     use std::path::PathBuf;
     use tokio::runtime;
 
-    use super::{AtomFeed, Author, Entry, Link};
+    use std::{fs::File, io::BufReader};
+
+    use super::{xml_from_reader, AtomFeed, Author, Content, Entry, Link};
+    use crate::feed::text::{Text, TextType};
+    use crate::feed::Feed;
 
     fn get_test_dir() -> PathBuf {
         std::env::current_dir()
@@ -310,11 +399,8 @@ mod test {
             .unwrap()
             .block_on(async {
                 let url = get_test_dir().join("empty.xml");
-                let feed = AtomFeed::from_url(
-                    format!("file://{}", url.to_string_lossy()),
-                    Option::<String>::None,
-                )
-                .await;
+                let file = File::open(&url).unwrap();
+                let feed = xml_from_reader(url.to_string_lossy(), BufReader::new(file));
                 assert!(feed.is_err());
             });
     }
@@ -326,9 +412,11 @@ mod test {
             .unwrap()
             .block_on(async {
                 let url = get_test_dir().join("atomfeed.xml");
-                let feed = AtomFeed::from_url(
+                let feed = crate::feed::from_url(
                     format!("file://{}", url.to_string_lossy()),
-                    Option::<String>::None,
+                    None,
+                    None,
+                    &Default::default(),
                 )
                 .await;
                 assert!(feed.is_ok());
@@ -358,7 +446,7 @@ mod test {
                                 .unwrap()
                                 .into(),
                         ),
-                        summary: Some("Some text.".to_owned()),
+                        summary: Some(Text::from("Some text.")),
                         link: vec![Link {
                             href: "http://example.org/2003/12/13/atom03".to_owned(),
                             ..Default::default()
@@ -368,7 +456,7 @@ mod test {
                     url: format!("file://{}", url.to_string_lossy()),
                     ..Default::default()
                 };
-                assert_eq!(expected_feed, feed);
+                assert_eq!(Feed::Atom(expected_feed), feed);
             });
     }
 
@@ -379,12 +467,116 @@ mod test {
             .unwrap()
             .block_on(async {
                 let url = get_test_dir().join("youtube_channel.xml");
-                let feed = AtomFeed::from_url(
+                let feed = crate::feed::from_url(
                     format!("file://{}", url.to_string_lossy()),
-                    Option::<String>::None,
+                    None,
+                    None,
+                    &Default::default(),
                 )
                 .await;
-                assert!(feed.is_ok());
+                assert!(matches!(feed, Ok(Feed::Atom(_))));
             });
     }
+
+    #[test]
+    fn text_constructs() {
+        let url = get_test_dir().join("atom_content.xml");
+        let file = File::open(&url).unwrap();
+        let feed = xml_from_reader(url.to_string_lossy(), BufReader::new(file)).unwrap();
+
+        assert_eq!(feed.title, "Release notes & changes");
+        assert_eq!(
+            feed.subtitle.as_deref(),
+            Some("What's new in every release")
+        );
+        assert_eq!(feed.rights.as_deref(), Some("© 2023 Example Corp"));
+
+        let html = &feed.entry[0];
+        assert_eq!(html.title, "v1.2.0 stable");
+        assert_eq!(html.summary, None);
+        assert_eq!(
+            html.content,
+            Some(Content {
+                content_type: Some("html".to_owned()),
+                src: None,
+                value: "<p>Bug fixes &amp; improvements.</p><ul><li>Faster</li></ul>".to_owned(),
+            })
+        );
+        assert_eq!(
+            html.content
+                .as_ref()
+                .and_then(Content::to_text)
+                .map(|t| t.to_plain()),
+            Some("Bug fixes & improvements.\n\nFaster".to_owned())
+        );
+
+        let xhtml = &feed.entry[1];
+        assert_eq!(xhtml.rights.as_deref(), Some("CC-BY"));
+        let summary = xhtml.summary.clone().unwrap();
+        assert_eq!(summary.kind, TextType::Xhtml);
+        assert_eq!(
+            summary.value.trim(),
+            r#"<p>Adds <a href="https://example.com/docs">docs</a> &amp; examples.</p>"#
+        );
+        assert_eq!(summary.to_plain(), "Adds docs & examples.");
+        assert_eq!(
+            xhtml.content.as_ref().and_then(Content::to_text),
+            Some(Text {
+                kind: TextType::Xhtml,
+                value: "<p>Full text.</p>".to_owned(),
+            })
+        );
+
+        let linked = &feed.entry[2];
+        let content = linked.content.clone().unwrap();
+        assert_eq!(
+            content.src.as_deref(),
+            Some("https://example.com/launch.mp4")
+        );
+        assert_eq!(content.to_text(), None);
+    }
+
+    #[test]
+    fn database_round_trip() {
+        let url = get_test_dir().join("atom_content.xml");
+        let file = File::open(&url).unwrap();
+        let feed = xml_from_reader(url.to_string_lossy(), BufReader::new(file)).unwrap();
+
+        let json = serde_json::to_string(&feed).unwrap();
+        assert_eq!(serde_json::from_str::<AtomFeed>(&json).unwrap(), feed);
+
+        // Summaries were stored as bare strings before text constructs
+        let old: Entry = serde_json::from_str(
+            r#"{"id": "urn:x", "title": "Old", "summary": "Some text.", "link": []}"#,
+        )
+        .unwrap();
+        assert_eq!(old.summary, Some(Text::from("Some text.")));
+    }
+
+    #[test]
+    fn skip_rules() {
+        use chrono::{TimeZone, Utc};
+
+        use crate::feed::rss::{SkipDays, SkipHours};
+
+        let monday = Utc.with_ymd_and_hms(2024, 3, 4, 12, 0, 0).unwrap();
+        let tuesday = Utc.with_ymd_and_hms(2024, 3, 5, 12, 0, 0).unwrap();
+        let late = Utc.with_ymd_and_hms(2024, 3, 5, 23, 15, 0).unwrap();
+        let feed = AtomFeed {
+            last_updated: Some(monday - chrono::Duration::days(1)),
+            skip_hours: SkipHours {
+                hour: vec!["23".to_owned()],
+            },
+            skip_days: SkipDays {
+                day: vec!["Monday".to_owned()],
+            },
+            ..Default::default()
+        };
+        assert!(!feed.should_update(monday));
+        assert!(feed.should_update(tuesday));
+        assert!(!feed.should_update(late));
+
+        let json = serde_json::to_string(&feed).unwrap();
+        assert_eq!(serde_json::from_str::<AtomFeed>(&json).unwrap(), feed);
+    }
 }
@@ -0,0 +1,156 @@
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use lazy_static::lazy_static;
+use regex::bytes::Regex;
+use std::borrow::Cow;
+use tracing::debug;
+
+// Value of the encoding pseudo-attribute of the XML declaration, if any
+fn declared(bytes: &[u8]) -> Option<&[u8]> {
+    lazy_static! {
+        static ref DECLARATION_REGEX: Regex =
+            Regex::new(r#"^\s*<\?xml\b[^>]*?\bencoding\s*=\s*["']([A-Za-z0-9._:-]+)["']"#).unwrap();
+    }
+
+    Some(DECLARATION_REGEX.captures(bytes)?.get(1)?.as_bytes())
+}
+
+// The charset parameter of a Content-Type header value, e.g. "iso-8859-1"
+pub fn from_content_type(header: impl AsRef<str>) -> Option<String> {
+    header.as_ref().split(';').skip(1).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        name.trim()
+            .eq_ignore_ascii_case("charset")
+            .then(|| {
+                value
+                    .trim()
+                    .trim_matches(|c| c == '"' || c == '\'')
+                    .to_owned()
+            })
+            .filter(|value| !value.is_empty())
+    })
+}
+
+// Encoding of a document from its byte order mark, then the charset from
+// the Content-Type header, then its XML declaration, defaulting to UTF-8. A
+// header claiming UTF-8 for a document that isn't loses to the declaration.
+pub fn detect(bytes: &[u8], charset: Option<&str>) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return encoding;
+    }
+
+    // UTF-16 without a byte order mark still starts with "<?"
+    match bytes {
+        [b'<', 0, b'?', 0, ..] => return UTF_16LE,
+        [0, b'<', 0, b'?', ..] => return UTF_16BE,
+        _ => (),
+    }
+
+    let header = charset.and_then(|c| Encoding::for_label(c.trim().as_bytes()));
+    let declaration = declared(bytes).and_then(Encoding::for_label);
+    match (header, declaration) {
+        (Some(header), Some(declaration))
+            if header == UTF_8 && std::str::from_utf8(bytes).is_err() =>
+        {
+            declaration
+        }
+        (Some(header), _) => header,
+        (None, Some(declaration)) => declaration,
+        (None, None) => UTF_8,
+    }
+}
+
+// Transcode a document to UTF-8 for the parsers, dropping any byte order
+// mark and updating the XML declaration to match. Bytes that are invalid in
+// the detected encoding become U+FFFD.
+pub fn to_utf8(bytes: &[u8], charset: Option<&str>) -> Vec<u8> {
+    lazy_static! {
+        static ref ENCODING_REGEX: Regex =
+            Regex::new(r#"^(\s*<\?xml\b[^>]*?\bencoding\s*=\s*["'])[^"']*(["'])"#).unwrap();
+    }
+
+    let encoding = detect(bytes, charset);
+    let bytes = match Encoding::for_bom(bytes) {
+        Some((_, bom_length)) => &bytes[bom_length..],
+        None => bytes,
+    };
+    if encoding != UTF_8 {
+        debug!("Transcoding document from {} to UTF-8.", encoding.name());
+    }
+
+    let (text, had_errors) = encoding.decode_without_bom_handling(bytes);
+    if had_errors {
+        debug!("Document has bytes that are not valid {}.", encoding.name());
+    }
+
+    match ENCODING_REGEX.replace(text.as_bytes(), &b"${1}UTF-8${2}"[..]) {
+        Cow::Borrowed(_) => text.into_owned().into_bytes(),
+        Cow::Owned(replaced) => replaced,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::{detect, from_content_type, to_utf8};
+    use crate::feed::{parse, Format};
+
+    fn get_test_dir() -> PathBuf {
+        std::env::current_dir()
+            .expect("failed to get current directory")
+            .join("test")
+    }
+
+    #[test]
+    fn charset_parameter() {
+        assert_eq!(
+            from_content_type("text/xml; charset=ISO-8859-1").as_deref(),
+            Some("ISO-8859-1")
+        );
+        assert_eq!(
+            from_content_type("application/rss+xml;charset=\"utf-8\"").as_deref(),
+            Some("utf-8")
+        );
+        assert_eq!(from_content_type("application/rss+xml"), None);
+    }
+
+    #[test]
+    fn resolution_order() {
+        let latin1 = b"<?xml version=\"1.0\" encoding=\"iso-8859-1\"?><rss>caf\xE9</rss>";
+        assert_eq!(detect(latin1, None).name(), "windows-1252");
+        assert_eq!(detect(latin1, Some("koi8-r")).name(), "KOI8-R");
+        // A wrong UTF-8 header doesn't beat the declaration
+        assert_eq!(detect(latin1, Some("utf-8")).name(), "windows-1252");
+        assert_eq!(detect(b"\xEF\xBB\xBF<rss/>", Some("gbk")).name(), "UTF-8");
+        assert_eq!(detect(b"<rss/>", Some("not-a-charset")).name(), "UTF-8");
+
+        assert_eq!(
+            to_utf8(latin1, None),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?><rss>café</rss>".as_bytes()
+        );
+    }
+
+    #[test]
+    fn encoded_feeds() {
+        let cases = [
+            ("encoding_iso-8859-1.xml", None, "Café crème à Noël"),
+            ("encoding_windows-1252.xml", None, "“Smart” quotes – €5"),
+            ("encoding_shift_jis.xml", None, "日本語のニュース"),
+            ("encoding_gb2312.xml", None, "中文新闻"),
+            ("encoding_utf-16.xml", None, "Ünïcödé"),
+            (
+                "encoding_undeclared.xml",
+                Some("windows-1252"),
+                "Straße über Zürich",
+            ),
+        ];
+
+        for (file, charset, title) in cases {
+            // Without a header the encoding is worked out from the document
+            let bytes = to_utf8(&std::fs::read(get_test_dir().join(file)).unwrap(), charset);
+            let feed =
+                parse(Format::Rss, file, &bytes).unwrap_or_else(|e| panic!("{}: {}", file, e));
+            assert_eq!(feed.title(), title, "{}", file);
+        }
+    }
+}
@@ -0,0 +1,358 @@
+use chrono::{DateTime, FixedOffset, NaiveDate, TimeZone, Utc};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{self, Deserialize, Deserializer};
+
+// Parse a feed date, accepting RFC 822/2822 (including the obsolete zone names),
+// RFC 3339 and its W3C-DTF profile, and the malformed variants of both that
+// are common in real-world feeds. Dates without a zone are taken as UTC.
+pub fn parse(str: impl AsRef<str>) -> anyhow::Result<DateTime<Utc>> {
+    let str = str.as_ref().trim();
+    if str.is_empty() {
+        anyhow::bail!("empty date");
+    }
+
+    iso8601(str)
+        .or_else(|| rfc2822(str))
+        .ok_or_else(|| anyhow::anyhow!("unrecognized date {:?}", str))
+}
+
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = Option::<String>::deserialize(deserializer)?;
+    if let Some(s) = s {
+        parse(s).map(Some).map_err(serde::de::Error::custom)
+    } else {
+        Ok(None)
+    }
+}
+
+fn to_utc(
+    date: NaiveDate,
+    (hour, minute, second): (u32, u32, u32),
+    offset: i32,
+) -> Option<DateTime<Utc>> {
+    // Leap seconds are folded into the second before
+    let naive = date.and_hms_opt(hour, minute, second.min(59))?;
+    FixedOffset::east_opt(offset)?
+        .from_local_datetime(&naive)
+        .single()
+        .map(Into::into)
+}
+
+// Offset in seconds of "+hh:mm", "+hhmm" or "+hh"
+fn parse_offset(offset: &str) -> Option<i32> {
+    let (sign, digits) = match offset.chars().next()? {
+        '+' => (1, &offset[1..]),
+        '-' => (-1, &offset[1..]),
+        _ => return None,
+    };
+    let digits = digits.replace(':', "");
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let (hours, minutes) = match digits.len() {
+        1 | 2 => (digits.parse::<i32>().ok()?, 0),
+        3 | 4 => {
+            let split = digits.len() - 2;
+            (
+                digits[..split].parse::<i32>().ok()?,
+                digits[split..].parse::<i32>().ok()?,
+            )
+        }
+        _ => return None,
+    };
+    if hours > 23 || minutes > 59 {
+        return None;
+    }
+
+    Some(sign * (hours * 3600 + minutes * 60))
+}
+
+// Offset in seconds of a zone name
+fn zone_offset(zone: &str) -> Option<i32> {
+    let hours = match zone {
+        "ut" | "utc" | "gmt" | "z" | "wet" => 0.0,
+        "edt" => -4.0,
+        "est" | "cdt" => -5.0,
+        "cst" | "mdt" => -6.0,
+        "mst" | "pdt" => -7.0,
+        "pst" | "akdt" => -8.0,
+        "akst" => -9.0,
+        "hst" => -10.0,
+        "adt" => -3.0,
+        "ast" => -4.0,
+        "nst" => -3.5,
+        "ndt" => -2.5,
+        "bst" | "cet" | "met" | "west" => 1.0,
+        "cest" | "mest" | "eet" | "sast" => 2.0,
+        "eest" | "msk" => 3.0,
+        "ist" => 5.5,
+        "awst" | "hkt" | "sgt" => 8.0,
+        "jst" | "kst" => 9.0,
+        "acst" => 9.5,
+        "aest" => 10.0,
+        "acdt" => 10.5,
+        "aedt" => 11.0,
+        "nzst" => 12.0,
+        "nzdt" => 13.0,
+        // RFC 2822 section 4.3: military zones were specified with the wrong
+        // sign in RFC 822, so they carry no information
+        z if z.len() == 1 && z != "j" && z.chars().all(|c| c.is_ascii_lowercase()) => 0.0,
+        _ => return None,
+    };
+    Some((hours * 3600.0) as i32)
+}
+
+fn month(name: &str) -> Option<u32> {
+    const MONTHS: [&str; 12] = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
+    if name.len() < 3 {
+        return None;
+    }
+
+    MONTHS
+        .iter()
+        .position(|m| name.starts_with(m))
+        .map(|idx| idx as u32 + 1)
+}
+
+fn is_weekday(name: &str) -> bool {
+    const DAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+    name.len() >= 3 && DAYS.iter().any(|d| name.starts_with(d))
+}
+
+// "09:04", "09:04:00" or "9:04:00.123", seconds default to zero
+fn parse_time(time: &str) -> Option<(u32, u32, u32)> {
+    let mut parts = time.split(':');
+    let hour = parts.next()?.parse().ok()?;
+    let minute = parts.next()?.parse().ok()?;
+    let second = match parts.next() {
+        Some(s) => s.split(['.', ',']).next()?.parse().ok()?,
+        None => 0,
+    };
+    if parts.next().is_some() || hour > 24 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    Some((hour, minute, second))
+}
+
+// ISO 8601 style dates, e.g. 2023-07-21T09:04:00Z, 2023-07-21 09:04 or 2023-07
+fn iso8601(str: &str) -> Option<DateTime<Utc>> {
+    lazy_static! {
+        static ref ISO_REGEX: Regex = Regex::new(
+            r"(?i)^(\d{4})(?:-(\d{1,2})(?:-(\d{1,2}))?)?(?:[T ]\s*(\d{1,2}:\d{2}(?::\d{2}(?:[.,]\d+)?)?))?\s*(Z|UTC|GMT|[+-]\d{2}(?::?\d{2})?)?$"
+        )
+        .unwrap();
+    }
+
+    if let Ok(dt) = DateTime::parse_from_rfc3339(str) {
+        return Some(dt.into());
+    }
+
+    let caps = ISO_REGEX.captures(str)?;
+    let number = |idx: usize, default: u32| {
+        caps.get(idx)
+            .map_or(Some(default), |m| m.as_str().parse().ok())
+    };
+    let date = NaiveDate::from_ymd_opt(caps[1].parse().ok()?, number(2, 1)?, number(3, 1)?)?;
+    let time = match caps.get(4) {
+        Some(t) => parse_time(t.as_str())?,
+        None => (0, 0, 0),
+    };
+    let offset = match caps.get(5).map(|z| z.as_str().to_lowercase()) {
+        Some(z) if z.starts_with(['+', '-']) => parse_offset(&z)?,
+        Some(z) => zone_offset(&z)?,
+        None => 0,
+    };
+
+    to_utc(date, time, offset)
+}
+
+// RFC 822/2822 style dates, e.g. "Fri, 21 Jul 2023 09:04:00 -0400", with the
+// weekday, seconds and zone optional and the fields in any reasonable order
+fn rfc2822(str: &str) -> Option<DateTime<Utc>> {
+    let mut day = None;
+    let mut month_num = None;
+    let mut year: Option<i32> = None;
+    let mut time = None;
+    let mut offset = None;
+    let mut pm = None;
+
+    let mut tokens = Vec::new();
+    for token in str.split(|c: char| c.is_whitespace() || c == ',') {
+        let token = token.trim_end_matches('.').to_lowercase();
+        if token.is_empty() {
+            continue;
+        }
+
+        if token.contains('-')
+            && !token.starts_with(['+', '-'])
+            && !token.contains(':')
+            && token.chars().any(|c| c.is_ascii_alphabetic())
+        {
+            // 21-Jul-2023
+            tokens.extend(token.split('-').map(String::from));
+            continue;
+        }
+        if let Some(idx) = token.find(['+', '-']).filter(|idx| *idx > 0) {
+            // GMT+0200 or 09:04:00-0400
+            let (head, tail) = token.split_at(idx);
+            tokens.push(head.to_string());
+            tokens.push(tail.to_string());
+            continue;
+        }
+        tokens.push(token);
+    }
+
+    for token in tokens {
+        let token = token.as_str();
+        if token.starts_with(['+', '-']) {
+            offset = Some(parse_offset(token)?);
+        } else if token.contains(':') {
+            let (clock, meridiem) = match token
+                .strip_suffix("am")
+                .map(|t| (t, Some(false)))
+                .or_else(|| token.strip_suffix("pm").map(|t| (t, Some(true))))
+            {
+                Some((clock, meridiem)) => (clock, meridiem),
+                None => (token.strip_suffix('z').unwrap_or(token), None),
+            };
+            time = Some(parse_time(clock)?);
+            pm = pm.or(meridiem);
+        } else if token.starts_with(|c: char| c.is_ascii_digit()) {
+            // Ordinals like 21st are occasionally used for the day
+            let digits = token.trim_end_matches(char::is_alphabetic);
+            if digits.len() < token.len()
+                && !matches!(&token[digits.len()..], "st" | "nd" | "rd" | "th")
+            {
+                return None;
+            }
+            let number: i32 = digits.parse().ok()?;
+            if digits.len() >= 3 || number > 31 || day.is_some() {
+                if year.is_some() {
+                    return None;
+                }
+                year = Some(match (digits.len(), number) {
+                    // RFC 2822 section 4.3 obsolete two and three digit years
+                    (1 | 2, 0..=49) => number + 2000,
+                    (1..=3, _) => number + 1900,
+                    _ => number,
+                });
+            } else {
+                day = Some(number as u32);
+            }
+        } else if let Some(m) = month(token).filter(|_| month_num.is_none()) {
+            month_num = Some(m);
+        } else if is_weekday(token) || token == "at" {
+            continue;
+        } else if token == "am" || token == "pm" {
+            pm = Some(token == "pm");
+        } else {
+            offset = Some(zone_offset(token)?);
+        }
+    }
+
+    let (mut hour, minute, second) = time.unwrap_or_default();
+    match pm {
+        Some(true) if hour < 12 => hour += 12,
+        Some(false) if hour == 12 => hour = 0,
+        _ => (),
+    }
+
+    let date = NaiveDate::from_ymd_opt(year?, month_num?, day?)?;
+    to_utc(date, (hour, minute, second), offset.unwrap_or_default())
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{DateTime, Utc};
+
+    use super::parse;
+
+    #[test]
+    fn real_world_dates() {
+        let cases = [
+            // RFC 822 and RFC 2822
+            ("Fri, 21 Jul 2023 09:04:00 -0400", "2023-07-21T13:04:00Z"),
+            ("Fri, 21 Jul 2023 09:04 -0400", "2023-07-21T13:04:00Z"),
+            ("Tue, 10 Jun 2003 04:00:00 GMT", "2003-06-10T04:00:00Z"),
+            ("Tue, 10 Jun 2003 04:00:00 UT", "2003-06-10T04:00:00Z"),
+            ("Tue, 10 Jun 2003 04:00:00 EST", "2003-06-10T09:00:00Z"),
+            ("Tue, 10 Jun 2003 04:00:00 PDT", "2003-06-10T11:00:00Z"),
+            ("Tue, 10 Jun 2003 04:00:00 Z", "2003-06-10T04:00:00Z"),
+            ("Tue, 10 Jun 2003 04:00:00 A", "2003-06-10T04:00:00Z"),
+            ("10 Jun 2003 04:00:00 +0000", "2003-06-10T04:00:00Z"),
+            ("Tue, 3 Jun 2003 04:00:00 +0000", "2003-06-03T04:00:00Z"),
+            ("Tue, 03 Jun 03 04:00:00 +0000", "2003-06-03T04:00:00Z"),
+            ("Tue, 03 Jun 99 04:00:00 +0000", "1999-06-03T04:00:00Z"),
+            ("Tue, 10 Jun 2003 04:00:60 +0000", "2003-06-10T04:00:59Z"),
+            // Common mistakes
+            ("Tue, 10 Jun 2003 04:00:00", "2003-06-10T04:00:00Z"),
+            ("Tue,10 Jun 2003 04:00:00 +0000", "2003-06-10T04:00:00Z"),
+            (
+                "Tuesday, 10 June 2003 04:00:00 +0000",
+                "2003-06-10T04:00:00Z",
+            ),
+            ("Thurs, 12 Jun 2003 04:00:00 +0000", "2003-06-12T04:00:00Z"),
+            ("Wed, 10 Sept 2003 04:00:00 +0000", "2003-09-10T04:00:00Z"),
+            ("Tue, 10 Jun 2003 04:00:00 +00:00", "2003-06-10T04:00:00Z"),
+            ("Tue, 10 Jun 2003 04:00:00 GMT+0200", "2003-06-10T02:00:00Z"),
+            (
+                "Tue, 10 Jun 2003 04:00:00.000 +0000",
+                "2003-06-10T04:00:00Z",
+            ),
+            ("Tue, 10 Jun 2003", "2003-06-10T00:00:00Z"),
+            ("Jun 10, 2003 4:00 PM EST", "2003-06-10T21:00:00Z"),
+            ("June 10th, 2003", "2003-06-10T00:00:00Z"),
+            ("Tue Jun 10 04:00:00 UTC 2003", "2003-06-10T04:00:00Z"),
+            ("10-Jun-2003 04:00:00 +0000", "2003-06-10T04:00:00Z"),
+            (
+                "  Tue, 10  Jun 2003 04:00:00 +0000  ",
+                "2003-06-10T04:00:00Z",
+            ),
+            // RFC 3339 and W3C-DTF
+            ("2023-07-21T09:04:00Z", "2023-07-21T09:04:00Z"),
+            ("2023-07-21T09:04:00.123-04:00", "2023-07-21T13:04:00.123Z"),
+            ("2023-07-21T09:04:00+0200", "2023-07-21T07:04:00Z"),
+            ("2023-07-21T09:04+01:00", "2023-07-21T08:04:00Z"),
+            ("2023-07-21T09:04Z", "2023-07-21T09:04:00Z"),
+            ("2023-07-21 09:04:00", "2023-07-21T09:04:00Z"),
+            ("2023-07-21 09:04:00 +0000", "2023-07-21T09:04:00Z"),
+            ("2023-07-21T09:04:00", "2023-07-21T09:04:00Z"),
+            ("2023-07-21", "2023-07-21T00:00:00Z"),
+            ("2023-07", "2023-07-01T00:00:00Z"),
+            ("2023", "2023-01-01T00:00:00Z"),
+        ];
+
+        for (date, expected) in cases {
+            let expected: DateTime<Utc> = DateTime::parse_from_rfc3339(expected).unwrap().into();
+            match parse(date) {
+                Ok(parsed) => assert_eq!(parsed, expected, "{:?}", date),
+                Err(e) => panic!("{:?} failed to parse: {}", date, e),
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_dates() {
+        let cases = [
+            "",
+            "yesterday",
+            "Tue, 31 Feb 2003 04:00:00 +0000",
+            "Tue, 10 Jun 2003 25:00:00 +0000",
+            "Tue, 10 Foo 2003 04:00:00 +0000",
+            "Tue, 10 Jun 2003 04:00:00 XYZ",
+            "2023-13-01",
+        ];
+
+        for date in cases {
+            assert!(parse(date).is_err(), "{:?} should not parse", date);
+        }
+    }
+}
@@ -0,0 +1,266 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use reqwest::Url;
+use std::fmt;
+use tracing::debug;
+
+use super::html::attribute;
+
+// A feed advertised by an HTML page with <link rel="alternate">
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Candidate {
+    pub url: Url,
+    pub title: Option<String>,
+    pub content_type: String,
+}
+
+impl fmt::Display for Candidate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.title {
+            Some(ref title) => write!(f, "{} ({})", title, self.url),
+            None => write!(f, "{}", self.url),
+        }
+    }
+}
+
+// Returned when a page links to more than one feed, so the admin can pick one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultipleFeeds {
+    pub page: Url,
+    pub candidates: Vec<Candidate>,
+}
+
+impl fmt::Display for MultipleFeeds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} links to {} feeds: ",
+            self.page,
+            self.candidates.len()
+        )?;
+        let urls = self
+            .candidates
+            .iter()
+            .map(|c| c.url.as_str())
+            .collect::<Vec<_>>();
+        write!(f, "{}", urls.join(", "))
+    }
+}
+
+impl std::error::Error for MultipleFeeds {}
+
+const FEED_TYPES: &[&str] = &[
+    "application/rss+xml",
+    "application/atom+xml",
+    "application/rdf+xml",
+    "application/feed+json",
+    "application/json",
+];
+
+// Whether a document that is not a feed is an HTML page worth searching
+pub fn is_html(body: &[u8], content_type: Option<&str>) -> bool {
+    if matches!(
+        content_type,
+        Some("text/html") | Some("application/xhtml+xml")
+    ) {
+        return true;
+    }
+
+    let head = &body[..body.len().min(1024)];
+    String::from_utf8_lossy(head)
+        .to_lowercase()
+        .contains("<html")
+}
+
+// Feeds advertised in the page, with hrefs resolved against the page url
+// (or its <base>) and duplicates removed. Only http(s) feeds are kept, or
+// local files for a page that is a local file itself, so a web page can't
+// have local files read.
+pub fn candidates(page: &Url, body: &[u8]) -> Vec<Candidate> {
+    lazy_static! {
+        static ref HIDDEN_REGEX: Regex =
+            Regex::new(r"(?is)<!--.*?-->|<(script|style)\b.*?</(script|style)\s*>").unwrap();
+        static ref TAG_REGEX: Regex = Regex::new(r"(?i)<(link|base)\b([^>]*)>").unwrap();
+    }
+
+    let html = String::from_utf8_lossy(body);
+    let html = HIDDEN_REGEX.replace_all(&html, "");
+
+    let mut base = page.clone();
+    let mut found: Vec<Candidate> = Vec::new();
+    for caps in TAG_REGEX.captures_iter(&html) {
+        let attrs = &caps[2];
+        if caps[1].eq_ignore_ascii_case("base") {
+            if let Some(href) = attribute(attrs, "href").and_then(|h| page.join(&h).ok()) {
+                base = href;
+            }
+            continue;
+        }
+
+        let is_alternate = attribute(attrs, "rel").is_some_and(|rel| {
+            rel.split_ascii_whitespace()
+                .any(|r| r.eq_ignore_ascii_case("alternate"))
+        });
+        let content_type = attribute(attrs, "type")
+            .map(super::fetch::media_type)
+            .filter(|t| FEED_TYPES.contains(&t.as_str()));
+        let (Some(content_type), true) = (content_type, is_alternate) else {
+            continue;
+        };
+        let Some(url) = attribute(attrs, "href").and_then(|h| base.join(h.trim()).ok()) else {
+            continue;
+        };
+        if !matches!(url.scheme(), "http" | "https") && url.scheme() != page.scheme() {
+            debug!("Ignoring feed {} linked from {}.", url, page);
+            continue;
+        }
+
+        if !found.iter().any(|c| c.url == url) {
+            found.push(Candidate {
+                url,
+                title: attribute(attrs, "title").filter(|t| !t.trim().is_empty()),
+                content_type,
+            });
+        }
+    }
+
+    found
+}
+
+// The one feed advertised by the page, or an error explaining why there isn't
+pub fn single(page: &Url, body: &[u8]) -> anyhow::Result<Url> {
+    let mut candidates = candidates(page, body);
+    match candidates.len() {
+        0 => anyhow::bail!("{} is an HTML page that does not link to any feeds", page),
+        1 => Ok(candidates.remove(0).url),
+        _ => Err(MultipleFeeds {
+            page: page.clone(),
+            candidates,
+        }
+        .into()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use reqwest::Url;
+    use std::path::PathBuf;
+    use tokio::runtime;
+
+    use super::{candidates, single, MultipleFeeds};
+    use crate::feed::Feed;
+
+    fn get_test_dir() -> PathBuf {
+        std::env::current_dir()
+            .expect("failed to get current directory")
+            .join("test")
+    }
+
+    #[test]
+    fn link_tags() {
+        let page = Url::parse("https://example.com/blog/index.html").unwrap();
+        let html = br#"<!DOCTYPE html>
+            <html><head>
+            <link rel="stylesheet" type="text/css" href="/style.css">
+            <link rel="alternate" type="application/rss+xml" title="Posts" href="feed.xml">
+            <LINK REL="Alternate home" TYPE="application/atom+xml; charset=utf-8" HREF="/atom.xml" />
+            <link rel="alternate" type="application/rss+xml" href="https://example.com/blog/feed.xml">
+            <link rel="alternate" type="application/feed+json" href='//cdn.example.com/feed.json'>
+            <link rel="alternate" hreflang="de" href="/de/">
+            <!-- <link rel="alternate" type="application/rss+xml" href="/old.xml"> -->
+            </head><body></body></html>"#;
+
+        let found = candidates(&page, html)
+            .into_iter()
+            .map(|c| (c.url.to_string(), c.title, c.content_type))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    "https://example.com/blog/feed.xml".to_owned(),
+                    Some("Posts".to_owned()),
+                    "application/rss+xml".to_owned()
+                ),
+                (
+                    "https://example.com/atom.xml".to_owned(),
+                    None,
+                    "application/atom+xml".to_owned()
+                ),
+                (
+                    "https://cdn.example.com/feed.json".to_owned(),
+                    None,
+                    "application/feed+json".to_owned()
+                ),
+            ]
+        );
+
+        let err = single(&page, html).unwrap_err();
+        let multiple = err.downcast_ref::<MultipleFeeds>().unwrap();
+        assert_eq!(multiple.candidates.len(), 3);
+    }
+
+    #[test]
+    fn base_href() {
+        let page = Url::parse("https://example.com/blog/").unwrap();
+        let html = br#"<html><head><base href="https://static.example.com/">
+            <link rel="alternate" type="application/rss+xml" href="rss"></head></html>"#;
+        assert_eq!(
+            single(&page, html).unwrap().as_str(),
+            "https://static.example.com/rss"
+        );
+
+        assert!(single(&page, b"<html><head></head></html>").is_err());
+    }
+
+    #[test]
+    fn local_links() {
+        let page = Url::parse("https://example.com/blog/").unwrap();
+        let html = br#"<html><head>
+            <link rel="alternate" type="application/rss+xml" href="file:///etc/passwd">
+            <link rel="alternate" type="application/atom+xml" href="ftp://example.com/atom.xml">
+            </head></html>"#;
+        assert!(candidates(&page, html).is_empty());
+        assert!(single(&page, html).is_err());
+
+        let html = br#"<html><head><base href="file:///etc/">
+            <link rel="alternate" type="application/rss+xml" href="passwd"></head></html>"#;
+        assert!(candidates(&page, html).is_empty());
+
+        // A local page may name a local feed
+        let page = Url::parse("file:///srv/www/index.html").unwrap();
+        let html = br#"<html><head>
+            <link rel="alternate" type="application/rss+xml" href="feed.xml"></head></html>"#;
+        assert_eq!(
+            single(&page, html).unwrap().as_str(),
+            "file:///srv/www/feed.xml"
+        );
+    }
+
+    #[test]
+    fn subscribe_to_page() {
+        runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let page = get_test_dir().join("discovery.html");
+                let feed = crate::feed::from_url(
+                    format!("file://{}", page.to_string_lossy()),
+                    None,
+                    None,
+                    &Default::default(),
+                )
+                .await
+                .expect("failed to discover feed");
+
+                assert!(matches!(feed, Feed::Rss(_)));
+                assert_eq!(
+                    feed.url(),
+                    format!(
+                        "file://{}",
+                        get_test_dir().join("rssboard.xml").to_string_lossy()
+                    )
+                );
+            });
+    }
+}
@@ -0,0 +1,73 @@
+use quick_xml::{events::Event, Reader};
+
+// Remove the document type declaration. Entities declared in its internal
+// subset are then never expanded, which rules out "billion laughs" documents;
+// references to them fail like any other unknown entity, and the lenient
+// parser drops the elements that use them.
+pub fn strip(bytes: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut reader = Reader::from_reader(bytes);
+    let mut buf = Vec::new();
+
+    // The declaration can only come before the document element
+    loop {
+        let start = reader.buffer_position();
+        match reader.read_event_into(&mut buf)? {
+            Event::DocType(_) => {
+                let end = reader.buffer_position();
+                let mut out = Vec::with_capacity(bytes.len());
+                out.extend_from_slice(&bytes[..start]);
+                out.extend_from_slice(&bytes[end..]);
+                return Ok(out);
+            }
+            Event::Start(_) | Event::Empty(_) | Event::Eof => return Ok(bytes.to_vec()),
+            _ => buf.clear(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs::File, io::BufReader, path::PathBuf, time::Instant};
+
+    use super::strip;
+    use crate::feed::rss::xml_from_reader;
+
+    fn get_test_dir() -> PathBuf {
+        std::env::current_dir()
+            .expect("failed to get current directory")
+            .join("test")
+    }
+
+    #[test]
+    fn strip_declaration() {
+        let doc = br#"<?xml version="1.0"?>
+<!DOCTYPE rss PUBLIC "-//Netscape Communications//DTD RSS 0.91//EN" "http://my.netscape.com/publish/formats/rss-0.91.dtd">
+<rss version="0.91"/>"#;
+        assert_eq!(
+            strip(doc).unwrap(),
+            b"<?xml version=\"1.0\"?>\n\n<rss version=\"0.91\"/>"
+        );
+
+        let doc = b"<rss><channel/></rss>";
+        assert_eq!(strip(doc).unwrap(), doc);
+    }
+
+    #[test]
+    fn billion_laughs() {
+        let started = Instant::now();
+        let file = File::open(get_test_dir().join("entity_expansion.xml")).unwrap();
+        let feed = xml_from_reader("laughs", BufReader::new(file))
+            .expect("the entities should be ignored, not expanded");
+        assert!(started.elapsed().as_secs() < 5);
+
+        let titles = feed
+            .channel
+            .item
+            .iter()
+            .map(|i| i.title.as_deref().unwrap_or_default())
+            .collect::<Vec<_>>();
+        assert_eq!(titles, vec!["Harmless", ""]);
+        assert_eq!(feed.channel.item[1].description, "Still delivered");
+        assert_eq!(feed.channel.diagnostics.len(), 1);
+    }
+}
@@ -0,0 +1,131 @@
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+// First wait before polling a failing feed again, doubled for every failure
+const BACKOFF_BASE: Duration = Duration::from_secs(5 * 60);
+// Longest wait before polling a failing feed again
+const BACKOFF_MAX: Duration = Duration::from_secs(12 * 60 * 60);
+
+// How polling a feed has been going
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct Health {
+    // Polls that failed in a row
+    pub failures: u32,
+    pub last_error: Option<String>,
+    pub last_failure: Option<DateTime<Utc>>,
+    pub last_success: Option<DateTime<Utc>>,
+    // Don't poll the feed before this, set by the backoff or Retry-After
+    pub retry_at: Option<DateTime<Utc>>,
+    // Failed too many times in a row, only polled again once resumed
+    pub paused: bool,
+    // Canonical url named by the feed that didn't serve the same feed, not
+    // tried again unless the feed names another
+    pub rejected_url: Option<String>,
+}
+
+impl Health {
+    pub fn record_success(&mut self, now: DateTime<Utc>) {
+        self.failures = 0;
+        self.retry_at = None;
+        self.last_success = Some(now);
+    }
+
+    // Count a failed poll and schedule the next attempt, using the server's
+    // Retry-After when it sent one. Returns true when this failure paused the
+    // feed, never pausing when max_failures is 0.
+    pub fn record_failure(
+        &mut self,
+        error: impl ToString,
+        retry_after: Option<Duration>,
+        max_failures: u32,
+        now: DateTime<Utc>,
+    ) -> bool {
+        self.failures = self.failures.saturating_add(1);
+        self.last_error = Some(error.to_string());
+        self.last_failure = Some(now);
+
+        let wait = retry_after.unwrap_or_else(|| backoff(BACKOFF_BASE, self.failures - 1));
+        self.retry_at = chrono::Duration::from_std(wait.min(BACKOFF_MAX))
+            .ok()
+            .and_then(|wait| now.checked_add_signed(wait));
+
+        let pause = !self.paused && max_failures > 0 && self.failures >= max_failures;
+        self.paused |= pause;
+        pause
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.failures = 0;
+        self.retry_at = None;
+    }
+
+    // Whether the feed may be polled, ignoring any backoff when forced
+    pub fn may_poll(&self, force: bool, now: DateTime<Utc>) -> bool {
+        !self.paused && (force || self.retry_at.is_none_or(|retry_at| retry_at <= now))
+    }
+}
+
+// base * 2^attempt plus up to half as much again, so feeds that failed
+// together don't all retry together
+pub fn backoff(base: Duration, attempt: u32) -> Duration {
+    let wait = base.saturating_mul(2u32.saturating_pow(attempt.min(16)));
+    let jitter: u32 = rand::thread_rng().gen_range(0..=1000);
+    wait + wait / 2 * jitter / 1000
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{TimeZone, Utc};
+    use std::time::Duration;
+
+    use super::{backoff, Health, BACKOFF_MAX};
+
+    #[test]
+    fn exponential_backoff() {
+        let base = Duration::from_secs(10);
+        for attempt in 0..5 {
+            let wait = backoff(base, attempt);
+            let expected = base * 2u32.pow(attempt);
+            assert!(wait >= expected && wait <= expected * 3 / 2, "{:?}", wait);
+        }
+        backoff(base, u32::MAX);
+    }
+
+    #[test]
+    fn pause_after_failures() {
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+        let mut health = Health::default();
+        assert!(!health.record_failure("timed out", None, 3, now));
+        assert!(!health.may_poll(false, now));
+        assert!(health.may_poll(true, now));
+        assert!(health.may_poll(false, now + chrono::Duration::hours(1)));
+
+        assert!(!health.record_failure("503", Some(Duration::from_secs(120)), 3, now));
+        assert_eq!(health.retry_at, Some(now + chrono::Duration::seconds(120)));
+
+        health.record_failure("unreachable", Some(Duration::MAX), 3, now);
+        assert!(health.paused);
+        assert_eq!(health.failures, 3);
+        assert_eq!(
+            health.retry_at,
+            Some(now + chrono::Duration::from_std(BACKOFF_MAX).unwrap())
+        );
+        assert!(!health.may_poll(true, now + chrono::Duration::days(1)));
+        assert!(!health.record_failure("still down", None, 3, now));
+
+        health.resume();
+        assert!(health.may_poll(false, now));
+        health.record_success(now);
+        assert_eq!(health.last_success, Some(now));
+        assert_eq!(health.last_error.as_deref(), Some("still down"));
+
+        let mut health = Health::default();
+        for _ in 0..100 {
+            assert!(!health.record_failure("never pause", None, 0, now));
+        }
+    }
+}
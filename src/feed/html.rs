@@ -0,0 +1,589 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use reqwest::Url;
+use std::mem;
+
+// Discord rejects embeds with descriptions longer than this
+pub const EMBED_DESCRIPTION_LIMIT: usize = 4096;
+
+// An HTML fragment rendered as Discord flavored markdown
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Markdown {
+    pub text: String,
+    // The first inline image, a candidate for the embed image
+    pub image: Option<String>,
+}
+
+enum Token<'a> {
+    Text(&'a str),
+    Open(String, &'a str, bool),
+    Close(String),
+}
+
+enum Frame {
+    Link(Option<String>, String),
+    Quote(String),
+}
+
+#[derive(Default)]
+struct Renderer {
+    out: String,
+    base: Option<Url>,
+    image: Option<String>,
+    frames: Vec<Frame>,
+    // None for unordered lists, otherwise the next item number
+    lists: Vec<Option<usize>>,
+    pre: usize,
+    code: usize,
+    hidden: usize,
+}
+
+fn tokenize(html: &str) -> Vec<Token<'_>> {
+    lazy_static! {
+        static ref TOKEN_REGEX: Regex = Regex::new(
+            r#"(?s)<!--.*?-->|<!\[CDATA\[.*?\]\]>|<[!?][^>]*>|<(/?)([a-zA-Z][a-zA-Z0-9:-]*)((?:[^>"']|"[^"]*"|'[^']*')*)>"#
+        )
+        .unwrap();
+    }
+
+    let mut tokens = Vec::new();
+    let mut last = 0;
+    for caps in TOKEN_REGEX.captures_iter(html) {
+        let whole = caps.get(0).expect("capture group 0 always matches");
+        if whole.start() > last {
+            tokens.push(Token::Text(&html[last..whole.start()]));
+        }
+        last = whole.end();
+
+        if let Some(cdata) = whole
+            .as_str()
+            .strip_prefix("<![CDATA[")
+            .and_then(|s| s.strip_suffix("]]>"))
+        {
+            tokens.push(Token::Text(cdata));
+            continue;
+        }
+
+        let Some(name) = caps.get(2) else {
+            continue;
+        };
+        // Drop any namespace prefix, e.g. xhtml:p
+        let name = name.as_str().rsplit(':').next().unwrap_or_default();
+        let name = name.to_lowercase();
+        if caps.get(1).is_some_and(|c| !c.as_str().is_empty()) {
+            tokens.push(Token::Close(name));
+        } else {
+            let attrs = caps.get(3).map(|a| a.as_str()).unwrap_or_default();
+            let self_closing = attrs.trim_end().ends_with('/');
+            tokens.push(Token::Open(name, attrs, self_closing));
+        }
+    }
+    if last < html.len() {
+        tokens.push(Token::Text(&html[last..]));
+    }
+
+    tokens
+}
+
+// Value of an attribute in the attribute list of a start tag, entities decoded
+pub fn attribute(attrs: &str, name: &str) -> Option<String> {
+    lazy_static! {
+        static ref ATTR_REGEX: Regex = Regex::new(
+            r#"([a-zA-Z_:][-a-zA-Z0-9_:.]*)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'=<>`]+))"#
+        )
+        .unwrap();
+    }
+
+    ATTR_REGEX.captures_iter(attrs).find_map(|caps| {
+        if caps[1].eq_ignore_ascii_case(name) {
+            caps.get(2)
+                .or(caps.get(3))
+                .or(caps.get(4))
+                .map(|v| decode_entities(v.as_str()))
+        } else {
+            None
+        }
+    })
+}
+
+fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '*' | '_' | '~' | '`' | '|') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+impl Renderer {
+    fn resolve(&self, href: &str) -> Option<String> {
+        let url = match self.base {
+            Some(ref base) => base.join(href.trim()).ok()?,
+            None => Url::parse(href.trim()).ok()?,
+        };
+
+        match url.scheme() {
+            "http" | "https" | "mailto" => Some(url.to_string()),
+            _ => None,
+        }
+    }
+
+    fn trim_trailing_spaces(&mut self) {
+        let trimmed = self.out.trim_end_matches([' ', '\t']).len();
+        self.out.truncate(trimmed);
+    }
+
+    // Start a new line unless already at the start of one
+    fn line(&mut self) {
+        self.trim_trailing_spaces();
+        if !self.out.is_empty() && !self.out.ends_with('\n') {
+            self.out.push('\n');
+        }
+    }
+
+    // Separate blocks with an empty line
+    fn block(&mut self) {
+        self.line();
+        if !self.out.is_empty() && !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        if self.hidden > 0 {
+            return;
+        }
+
+        let decoded = decode_entities(text);
+        if self.pre > 0 {
+            self.out.push_str(&decoded.replace("```", "``\u{200b}`"));
+            return;
+        }
+
+        lazy_static! {
+            static ref SPACE_REGEX: Regex = Regex::new(r"\s+").unwrap();
+        }
+        let collapsed = SPACE_REGEX.replace_all(&decoded, " ");
+        let collapsed = if self.out.is_empty() || self.out.ends_with([' ', '\n']) {
+            collapsed.trim_start()
+        } else {
+            &collapsed
+        };
+
+        if self.code > 0 {
+            self.out.push_str(&collapsed.replace('`', "'"));
+        } else {
+            self.out.push_str(&escape_markdown(collapsed));
+        }
+    }
+
+    fn open(&mut self, name: &str, attrs: &str) {
+        match name {
+            "script" | "style" | "head" | "title" | "noscript" | "template" | "iframe"
+            | "object" | "svg" => {
+                self.hidden += 1;
+            }
+            _ if self.hidden > 0 => (),
+            "br" => {
+                self.trim_trailing_spaces();
+                self.out.push('\n');
+            }
+            "p" | "div" | "section" | "article" | "header" | "footer" | "figure" | "table"
+            | "hr" | "dl" => self.block(),
+            "tr" | "dt" | "dd" | "figcaption" => self.line(),
+            "td" | "th" if !self.out.is_empty() && !self.out.ends_with([' ', '\n']) => {
+                self.out.push(' ')
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.block();
+                self.out.push_str("**");
+            }
+            "b" | "strong" => self.out.push_str("**"),
+            "i" | "em" | "cite" => self.out.push('*'),
+            "u" | "ins" => self.out.push_str("__"),
+            "s" | "strike" | "del" => self.out.push_str("~~"),
+            "code" | "kbd" | "samp" | "tt" if self.pre == 0 => {
+                self.code += 1;
+                self.out.push('`');
+            }
+            "pre" => {
+                self.block();
+                self.out.push_str("```\n");
+                self.pre += 1;
+            }
+            "ul" | "ol" => {
+                self.line();
+                self.lists.push(if name == "ol" { Some(1) } else { None });
+            }
+            "li" => {
+                self.line();
+                let depth = self.lists.len().saturating_sub(1);
+                self.out.push_str(&"  ".repeat(depth));
+                match self.lists.last_mut() {
+                    Some(Some(n)) => {
+                        self.out.push_str(&format!("{}. ", n));
+                        *n += 1;
+                    }
+                    _ => self.out.push_str("- "),
+                }
+            }
+            "blockquote" => {
+                self.block();
+                let saved = mem::take(&mut self.out);
+                self.frames.push(Frame::Quote(saved));
+            }
+            "a" => {
+                let href = attribute(attrs, "href").and_then(|h| self.resolve(&h));
+                let saved = mem::take(&mut self.out);
+                self.frames.push(Frame::Link(href, saved));
+            }
+            "img" if self.image.is_none() => {
+                self.image = attribute(attrs, "src").and_then(|src| self.resolve(&src));
+            }
+            _ => (),
+        }
+    }
+
+    fn close(&mut self, name: &str) {
+        match name {
+            "script" | "style" | "head" | "title" | "noscript" | "template" | "iframe"
+            | "object" | "svg" => self.hidden = self.hidden.saturating_sub(1),
+            _ if self.hidden > 0 => (),
+            "p" | "div" | "section" | "article" | "header" | "footer" | "figure" | "table"
+            | "dl" => self.block(),
+            "tr" | "dt" | "dd" | "figcaption" | "li" => self.line(),
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.trim_trailing_spaces();
+                self.out.push_str("**");
+                self.block();
+            }
+            "b" | "strong" => {
+                self.trim_trailing_spaces();
+                self.out.push_str("**");
+            }
+            "i" | "em" | "cite" => {
+                self.trim_trailing_spaces();
+                self.out.push('*');
+            }
+            "u" | "ins" => self.out.push_str("__"),
+            "s" | "strike" | "del" => self.out.push_str("~~"),
+            "code" | "kbd" | "samp" | "tt" if self.pre == 0 && self.code > 0 => {
+                self.code -= 1;
+                self.out.push('`');
+            }
+            "pre" if self.pre > 0 => {
+                self.pre -= 1;
+                self.line();
+                self.out.push_str("```");
+                self.block();
+            }
+            "ul" | "ol" => {
+                self.lists.pop();
+                if self.lists.is_empty() {
+                    self.block();
+                } else {
+                    self.line();
+                }
+            }
+            "blockquote" => self.close_frame(|f| matches!(f, Frame::Quote(_))),
+            "a" => self.close_frame(|f| matches!(f, Frame::Link(_, _))),
+            _ => (),
+        }
+    }
+
+    // Close frames up to and including the innermost one matching is_target
+    fn close_frame(&mut self, is_target: impl Fn(&Frame) -> bool) {
+        if !self.frames.iter().any(&is_target) {
+            return;
+        }
+
+        while let Some(frame) = self.frames.pop() {
+            let done = is_target(&frame);
+            self.finish_frame(frame);
+            if done {
+                break;
+            }
+        }
+    }
+
+    fn finish_frame(&mut self, frame: Frame) {
+        match frame {
+            Frame::Link(href, saved) => {
+                let text = mem::replace(&mut self.out, saved);
+                let text = text.trim();
+                match href {
+                    Some(href) if text.is_empty() => self.out.push_str(&href),
+                    // Link text that only repeats the address
+                    Some(href)
+                        if text.trim_end_matches('/')
+                            == escape_markdown(href.trim_end_matches('/')) =>
+                    {
+                        self.out.push_str(&href)
+                    }
+                    Some(href) => self.out.push_str(&format!("[{}]({})", text, href)),
+                    None => self.out.push_str(text),
+                }
+            }
+            Frame::Quote(saved) => {
+                let text = mem::replace(&mut self.out, saved);
+                for line in text.trim().lines() {
+                    self.out.push_str("> ");
+                    self.out.push_str(line);
+                    self.out.push('\n');
+                }
+                self.block();
+            }
+        }
+    }
+
+    fn finish(mut self) -> Markdown {
+        while let Some(frame) = self.frames.pop() {
+            self.finish_frame(frame);
+        }
+        if self.pre > 0 {
+            self.line();
+            self.out.push_str("```");
+        }
+
+        lazy_static! {
+            static ref LINES_REGEX: Regex = Regex::new(r"\n{3,}").unwrap();
+        }
+        let text = LINES_REGEX.replace_all(self.out.trim(), "\n\n").to_string();
+        Markdown {
+            text,
+            image: self.image,
+        }
+    }
+}
+
+// Render an HTML fragment as Discord markdown, resolving relative links
+// and images against base
+pub fn to_markdown(html: &str, base: Option<&str>) -> Markdown {
+    let mut renderer = Renderer {
+        base: base.and_then(|b| Url::parse(b).ok()),
+        ..Default::default()
+    };
+
+    for token in tokenize(html) {
+        match token {
+            Token::Text(text) => renderer.text(text),
+            Token::Open(name, attrs, self_closing) => {
+                renderer.open(&name, attrs);
+                if self_closing {
+                    renderer.close(&name);
+                }
+            }
+            Token::Close(name) => renderer.close(&name),
+        }
+    }
+
+    renderer.finish()
+}
+
+// Cut a description down to what Discord will accept in an embed
+pub fn limit_description(text: String) -> String {
+    match text.char_indices().nth(EMBED_DESCRIPTION_LIMIT - 1) {
+        None => text,
+        Some((idx, _)) => format!("{}…", &text[..idx]),
+    }
+}
+
+// Decode HTML character references, leaving unknown ones untouched
+pub fn decode_entities(s: &str) -> String {
+    lazy_static! {
+        static ref ENTITY_REGEX: Regex =
+            Regex::new(r"&(#[0-9]+|#[xX][0-9a-fA-F]+|[a-zA-Z]+[0-9]*);").unwrap();
+    }
+
+    ENTITY_REGEX
+        .replace_all(s, |caps: &regex::Captures| {
+            let entity = &caps[1];
+            let decoded = if let Some(hex) = entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+            {
+                u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+            } else if let Some(dec) = entity.strip_prefix('#') {
+                dec.parse().ok().and_then(char::from_u32)
+            } else {
+                match entity {
+                    "amp" => Some('&'),
+                    "lt" => Some('<'),
+                    "gt" => Some('>'),
+                    "quot" => Some('"'),
+                    "apos" => Some('\''),
+                    "nbsp" => Some('\u{a0}'),
+                    "ensp" | "emsp" | "thinsp" => Some(' '),
+                    "shy" | "zwnj" | "zwj" => Some('\u{200b}'),
+                    "ndash" => Some('–'),
+                    "mdash" => Some('—'),
+                    "hellip" => Some('…'),
+                    "lsquo" => Some('‘'),
+                    "rsquo" => Some('’'),
+                    "sbquo" => Some('‚'),
+                    "ldquo" => Some('“'),
+                    "rdquo" => Some('”'),
+                    "bdquo" => Some('„'),
+                    "laquo" => Some('«'),
+                    "raquo" => Some('»'),
+                    "copy" => Some('©'),
+                    "reg" => Some('®'),
+                    "trade" => Some('™'),
+                    "deg" => Some('°'),
+                    "middot" => Some('·'),
+                    "bull" => Some('•'),
+                    "times" => Some('×'),
+                    "divide" => Some('÷'),
+                    "plusmn" => Some('±'),
+                    "frac12" => Some('½'),
+                    "frac14" => Some('¼'),
+                    "frac34" => Some('¾'),
+                    "sect" => Some('§'),
+                    "para" => Some('¶'),
+                    "euro" => Some('€'),
+                    "pound" => Some('£'),
+                    "yen" => Some('¥'),
+                    "cent" => Some('¢'),
+                    "larr" => Some('←'),
+                    "rarr" => Some('→'),
+                    "uarr" => Some('↑'),
+                    "darr" => Some('↓'),
+                    "iexcl" => Some('¡'),
+                    "iquest" => Some('¿'),
+                    "auml" => Some('ä'),
+                    "ouml" => Some('ö'),
+                    "uuml" => Some('ü'),
+                    "Auml" => Some('Ä'),
+                    "Ouml" => Some('Ö'),
+                    "Uuml" => Some('Ü'),
+                    "szlig" => Some('ß'),
+                    "eacute" => Some('é'),
+                    "egrave" => Some('è'),
+                    "ecirc" => Some('ê'),
+                    "Eacute" => Some('É'),
+                    "aacute" => Some('á'),
+                    "agrave" => Some('à'),
+                    "acirc" => Some('â'),
+                    "iacute" => Some('í'),
+                    "oacute" => Some('ó'),
+                    "uacute" => Some('ú'),
+                    "ntilde" => Some('ñ'),
+                    "ccedil" => Some('ç'),
+                    _ => None,
+                }
+            };
+
+            decoded
+                .map(String::from)
+                .unwrap_or_else(|| caps[0].to_string())
+        })
+        .to_string()
+}
+
+// Strip tags from an HTML fragment, keeping paragraph breaks
+pub fn to_plain(html: &str) -> String {
+    lazy_static! {
+        static ref HIDDEN_REGEX: Regex =
+            Regex::new(r"(?is)<(script|style)\b.*?</(script|style)\s*>").unwrap();
+        static ref BREAK_REGEX: Regex =
+            Regex::new(r"(?i)<br\s*/?>|</?(p|div|li|h[1-6]|blockquote|pre|tr)\b[^>]*>").unwrap();
+        static ref TAG_REGEX: Regex = Regex::new(r"(?s)<[^>]*>").unwrap();
+        static ref SPACE_REGEX: Regex = Regex::new(r"[ \t\r]+").unwrap();
+        static ref LINES_REGEX: Regex = Regex::new(r"\s*\n\s*(\n\s*)+").unwrap();
+    }
+
+    let text = HIDDEN_REGEX.replace_all(html, "");
+    let text = BREAK_REGEX.replace_all(&text, "\n");
+    let text = TAG_REGEX.replace_all(&text, "");
+    let text = SPACE_REGEX.replace_all(&text, " ");
+    let text = LINES_REGEX.replace_all(&text, "\n\n");
+    let text = text.lines().map(str::trim).collect::<Vec<_>>().join("\n");
+    decode_entities(text.trim())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{limit_description, to_markdown, EMBED_DESCRIPTION_LIMIT};
+
+    #[test]
+    fn markdown_conversion() {
+        let cases = [
+            ("plain text", "plain text"),
+            ("<p>One</p><p>Two</p>", "One\n\nTwo"),
+            ("line<br>break<br/>here", "line\nbreak\nhere"),
+            (
+                "<b>bold</b>, <strong>strong</strong>, <i>it</i>, <em>em</em>",
+                "**bold**, **strong**, *it*, *em*",
+            ),
+            ("<s>gone</s> <u>under</u>", "~~gone~~ __under__"),
+            (
+                r#"Read <a href="https://example.com/post">the post</a>."#,
+                "Read [the post](https://example.com/post).",
+            ),
+            (
+                r#"<a href="https://example.com">https://example.com</a>"#,
+                "https://example.com/",
+            ),
+            (r#"<a href="javascript:alert(1)">click</a>"#, "click"),
+            ("<ul><li>one</li><li>two</li></ul>", "- one\n- two"),
+            ("<ol><li>one</li><li>two</li></ol>", "1. one\n2. two"),
+            (
+                "<ul><li>outer<ul><li>inner</li></ul></li></ul>",
+                "- outer\n  - inner",
+            ),
+            ("use <code>cargo build</code>", "use `cargo build`"),
+            (
+                "<pre><code>fn main() {\n    println!(\"*hi*\");\n}</code></pre>",
+                "```\nfn main() {\n    println!(\"*hi*\");\n}\n```",
+            ),
+            (
+                "<blockquote><p>Quoted</p><p>twice</p></blockquote><p>after</p>",
+                "> Quoted\n> \n> twice\n\nafter",
+            ),
+            (
+                "Tom &amp; Jerry &lt;3 &#8212; &#x2713; &hellip;",
+                "Tom & Jerry <3 — ✓ …",
+            ),
+            (
+                "<script>alert('x')</script><style>p { color: red }</style>Visible",
+                "Visible",
+            ),
+            ("<h2>Title</h2><p>Body</p>", "**Title**\n\nBody"),
+            ("2 * 3 = 6_000", "2 \\* 3 = 6\\_000"),
+            ("<!-- comment -->kept", "kept"),
+            ("  lots   of\n\n  space  ", "lots of space"),
+            ("<p>unclosed <b>bold", "unclosed **bold"),
+        ];
+
+        for (html, expected) in cases {
+            assert_eq!(to_markdown(html, None).text, expected, "{}", html);
+        }
+    }
+
+    #[test]
+    fn relative_links_and_images() {
+        let md = to_markdown(
+            r#"<p><img src="/img/first.png"> <a href="../about">About</a> <img src="second.png"></p>"#,
+            Some("https://example.com/blog/post/"),
+        );
+        assert_eq!(md.text, "[About](https://example.com/blog/about)");
+        assert_eq!(
+            md.image.as_deref(),
+            Some("https://example.com/img/first.png")
+        );
+
+        assert_eq!(to_markdown("<img src=\"/relative.png\">", None).image, None);
+    }
+
+    #[test]
+    fn description_limit() {
+        let long = "a".repeat(EMBED_DESCRIPTION_LIMIT + 10);
+        let limited = limit_description(long);
+        assert_eq!(limited.chars().count(), EMBED_DESCRIPTION_LIMIT);
+        assert!(limited.ends_with('…'));
+
+        assert_eq!(limit_description("short".to_owned()), "short");
+    }
+}
@@ -0,0 +1,411 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serenity::builder::CreateEmbed;
+use std::io::Read;
+use tracing::{debug, instrument};
+
+use super::fetch::{RequestOptions, Validators};
+use super::health::Health;
+use super::html;
+use super::lenient::{self, Diagnostic};
+use super::schedule::Schedule;
+use super::websub::Subscription;
+
+// JSON Feed file (https://www.jsonfeed.org/version/1.1/)
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq, Default)]
+pub struct JsonFeed {
+    pub version: String,
+    pub title: String,
+    pub home_page_url: Option<String>,
+    pub feed_url: Option<String>,
+    pub description: Option<String>,
+    pub icon: Option<String>,
+    pub favicon: Option<String>,
+    #[serde(default)]
+    pub authors: Vec<Author>,
+    // Deprecated in 1.1, but still emitted by 1.0 feeds
+    pub author: Option<Author>,
+    pub language: Option<String>,
+    #[serde(default)]
+    pub expired: bool,
+    #[serde(default)]
+    pub hubs: Vec<Hub>,
+    #[serde(default)]
+    pub items: Vec<Item>,
+    #[serde(default)]
+    pub last_updated: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub url: String,
+    pub discord_category: Option<String>,
+    // Items skipped or repaired the last time the feed was parsed
+    #[serde(default)]
+    pub diagnostics: Vec<Diagnostic>,
+    // ETag and Last-Modified of the last full response, for conditional requests
+    #[serde(default)]
+    pub validators: Validators,
+    #[serde(default)]
+    pub health: Health,
+    // Credentials and headers for the feed's requests
+    #[serde(default)]
+    pub request: RequestOptions,
+    #[serde(default)]
+    pub websub: Option<Subscription>,
+    #[serde(default)]
+    pub schedule: Schedule,
+}
+
+// Endpoint that pushes updates of the feed, like WebSub
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq, Default)]
+pub struct Hub {
+    #[serde(rename = "type")]
+    pub hub_type: String,
+    pub url: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq, Default)]
+pub struct Item {
+    #[serde(with = "item_id")]
+    pub id: String,
+    pub url: Option<String>,
+    pub external_url: Option<String>,
+    pub title: Option<String>,
+    pub content_html: Option<String>,
+    pub content_text: Option<String>,
+    pub summary: Option<String>,
+    pub image: Option<String>,
+    pub banner_image: Option<String>,
+    pub date_published: Option<DateTime<Utc>>,
+    pub date_modified: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub authors: Vec<Author>,
+    pub author: Option<Author>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    pub read: Option<()>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq, Default)]
+pub struct Author {
+    pub name: Option<String>,
+    pub url: Option<String>,
+    pub avatar: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq, Default)]
+pub struct Attachment {
+    pub url: String,
+    pub mime_type: String,
+    pub title: Option<String>,
+    pub size_in_bytes: Option<u64>,
+    pub duration_in_seconds: Option<serde_json::Number>,
+}
+
+// JSON Feed 1.0 allowed numeric ids, 1.1 requires strings
+mod item_id {
+    use serde::{self, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(id: &str, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(id)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<String, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match serde_json::Value::deserialize(deserializer)? {
+            serde_json::Value::String(s) => Ok(s),
+            serde_json::Value::Number(n) => Ok(n.to_string()),
+            v => Err(serde::de::Error::custom(format!(
+                "item id must be a string, found {}",
+                v
+            ))),
+        }
+    }
+}
+
+impl Author {
+    fn display_name(&self) -> Option<&str> {
+        self.name.as_deref().or(self.url.as_deref())
+    }
+}
+
+impl Item {
+    pub fn get_link_href(&self) -> &str {
+        self.url
+            .as_deref()
+            .or(self.external_url.as_deref())
+            .unwrap_or(&self.id)
+    }
+
+    pub fn get_author(&self) -> Option<&Author> {
+        self.authors.first().or(self.author.as_ref())
+    }
+
+    pub fn get_enclosure_img(&self) -> Option<&str> {
+        if let Some(ref img) = self.image {
+            return Some(img);
+        }
+
+        if let Some(ref banner) = self.banner_image {
+            return Some(banner);
+        }
+
+        self.attachments
+            .iter()
+            .find(|a| super::is_image_mime_type(&a.mime_type))
+            .map(|a| a.url.as_str())
+    }
+
+    #[instrument(level = "debug")]
+    pub fn to_embed(&self) -> impl Fn(&mut CreateEmbed) -> &mut CreateEmbed {
+        let author = self.get_author().cloned();
+        let link = self.get_link_href().to_owned();
+        let html = self
+            .content_html
+            .as_deref()
+            .map(|h| html::to_markdown(h, Some(&link)))
+            .unwrap_or_default();
+        let description = self
+            .content_text
+            .clone()
+            .or_else(|| self.summary.clone())
+            .or(Some(html.text).filter(|t| !t.is_empty()))
+            .map(html::limit_description);
+        let title = self.title.clone();
+        let image = self.get_enclosure_img().map(String::from).or(html.image);
+        let date = self.date_published.or(self.date_modified);
+        let external = self.external_url.clone().filter(|_| self.url.is_some());
+        let tags = self.tags.join(", ");
+
+        move |embed: &mut CreateEmbed| {
+            if let Some(ref a) = author {
+                if let Some(name) = a.display_name() {
+                    embed.author(|author| {
+                        if let Some(ref url) = a.url {
+                            author.url(url);
+                        }
+                        if let Some(ref avatar) = a.avatar {
+                            author.icon_url(avatar);
+                        }
+                        author.name(name)
+                    });
+                }
+            }
+
+            if let Some(ref img) = image {
+                embed.image(img);
+            }
+
+            if let Some(ref t) = title {
+                embed.title(t);
+            } else {
+                embed.title("(Untitled)");
+            }
+
+            if let Some(d) = date {
+                embed.timestamp(d);
+            }
+
+            if let Some(ref desc) = description {
+                embed.description(desc);
+            } else {
+                embed.description("(No summary)");
+            }
+            embed.field("link", &link, false);
+
+            if let Some(ref e) = external {
+                embed.field("external link", e, true);
+            }
+
+            if !tags.is_empty() {
+                embed.field("tags", &tags, true);
+            }
+
+            embed
+        }
+    }
+}
+
+// The document must already be UTF-8, see charset::to_utf8
+pub fn json_from_reader(url: impl Into<String>, mut read: impl Read) -> anyhow::Result<JsonFeed> {
+    let mut bytes = Vec::new();
+    read.read_to_end(&mut bytes)?;
+
+    let (mut feed, diagnostics) = lenient::from_json::<JsonFeed>(&bytes, "items")
+        .map_err(|e| anyhow::anyhow!("{}: {}", url.into(), e))?;
+    if !feed.version.starts_with("https://jsonfeed.org/version/") {
+        anyhow::bail!("unknown JSON Feed version {}", feed.version);
+    }
+    feed.diagnostics = diagnostics;
+    Ok(feed)
+}
+
+impl lenient::Document for JsonFeed {
+    type Item = Item;
+
+    fn items(&mut self) -> &mut Vec<Item> {
+        &mut self.items
+    }
+}
+
+impl lenient::Item for Item {
+    fn title(&self) -> Option<String> {
+        self.title.clone()
+    }
+}
+
+impl JsonFeed {
+    // JSON Feed has no scheduling metadata, only whether the feed has expired
+    #[instrument(level = "trace")]
+    pub fn should_update(&self) -> bool {
+        if self.expired {
+            debug!("Feed {} has expired, not updating.", self.title);
+            return false;
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{DateTime, FixedOffset};
+    use std::path::PathBuf;
+    use tokio::runtime;
+
+    use std::fs::File;
+
+    use super::{json_from_reader, Attachment, Author, Item, JsonFeed};
+    use crate::feed::Feed;
+
+    fn get_test_dir() -> PathBuf {
+        std::env::current_dir()
+            .expect("failed to get current directory")
+            .join("test")
+    }
+
+    #[test]
+    fn empty_file() {
+        runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let url = get_test_dir().join("empty.xml");
+                let feed = json_from_reader(url.to_string_lossy(), File::open(&url).unwrap());
+                assert!(feed.is_err());
+            });
+    }
+
+    #[test]
+    fn xml_file() {
+        runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let url = get_test_dir().join("atomfeed.xml");
+                let feed = json_from_reader(url.to_string_lossy(), File::open(&url).unwrap());
+                assert!(feed.is_err());
+            });
+    }
+
+    #[test]
+    fn full_file() {
+        runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let url = get_test_dir().join("jsonfeed.json");
+                let feed = crate::feed::from_url(
+                    format!("file://{}", url.to_string_lossy()),
+                    None,
+                    None,
+                    &Default::default(),
+                )
+                .await;
+                assert!(feed.is_ok());
+
+                let feed = feed.unwrap();
+                let expected_feed = JsonFeed {
+                    version: "https://jsonfeed.org/version/1.1".to_owned(),
+                    title: "My Example Feed".to_owned(),
+                    home_page_url: Some("https://example.org/".to_owned()),
+                    feed_url: Some("https://example.org/feed.json".to_owned()),
+                    description: Some("Musings about examples.".to_owned()),
+                    authors: vec![Author {
+                        name: Some("Jane Doe".to_owned()),
+                        url: Some("https://example.org/about".to_owned()),
+                        ..Default::default()
+                    }],
+                    language: Some("en-US".to_owned()),
+                    items: vec![
+                        Item {
+                            id: "2".to_owned(),
+                            url: Some("https://example.org/second-item".to_owned()),
+                            title: Some("Second item".to_owned()),
+                            content_text: Some("This is a second item.".to_owned()),
+                            date_published: Some(
+                                DateTime::<FixedOffset>::parse_from_rfc3339(
+                                    "2023-08-02T10:00:00-04:00",
+                                )
+                                .unwrap()
+                                .into(),
+                            ),
+                            tags: vec!["example".to_owned(), "second".to_owned()],
+                            ..Default::default()
+                        },
+                        Item {
+                            id: "1".to_owned(),
+                            url: Some("https://example.org/initial-post".to_owned()),
+                            content_html: Some("<p>Hello, world!</p>".to_owned()),
+                            date_published: Some(
+                                DateTime::<FixedOffset>::parse_from_rfc3339("2023-08-01T09:30:00Z")
+                                    .unwrap()
+                                    .into(),
+                            ),
+                            attachments: vec![Attachment {
+                                url: "https://example.org/hello.png".to_owned(),
+                                mime_type: "image/png".to_owned(),
+                                size_in_bytes: Some(4096),
+                                ..Default::default()
+                            }],
+                            ..Default::default()
+                        },
+                    ],
+                    url: format!("file://{}", url.to_string_lossy()),
+                    ..Default::default()
+                };
+                assert_eq!(Feed::Json(expected_feed), feed);
+
+                let Feed::Json(feed) = feed else {
+                    panic!("parsed a json feed");
+                };
+                assert_eq!(
+                    feed.items[1].get_enclosure_img(),
+                    Some("https://example.org/hello.png")
+                );
+                assert_eq!(feed.items[0].get_enclosure_img(), None);
+            });
+    }
+
+    #[test]
+    fn version_1_0() {
+        let json = r#"{
+            "version": "https://jsonfeed.org/version/1",
+            "title": "Old Feed",
+            "author": { "name": "Old Author" },
+            "items": [{ "id": 42, "external_url": "https://example.com/linked" }]
+        }"#;
+        let feed = super::json_from_reader("test", json.as_bytes()).unwrap();
+        assert_eq!(feed.items[0].id, "42");
+        assert_eq!(feed.items[0].get_link_href(), "https://example.com/linked");
+        assert_eq!(
+            feed.author.and_then(|a| a.name),
+            Some("Old Author".to_owned())
+        );
+    }
+}
@@ -0,0 +1,360 @@
+use lazy_static::lazy_static;
+use quick_xml::{de::from_reader, events::Event, Reader};
+use regex::Regex;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{fmt, ops::Range};
+use tracing::debug;
+
+// A problem with a single item that was skipped or repaired while parsing
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq, Default)]
+pub struct Diagnostic {
+    // 1-based position of the item in the document
+    pub item: usize,
+    pub title: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.title {
+            Some(ref title) => write!(f, "item {} ({}): {}", self.item, title, self.message),
+            None => write!(f, "item {}: {}", self.item, self.message),
+        }
+    }
+}
+
+// A feed document whose items can be deserialized one at a time
+pub trait Document: DeserializeOwned {
+    type Item: Item;
+
+    fn items(&mut self) -> &mut Vec<Self::Item>;
+}
+
+pub trait Item: DeserializeOwned {
+    fn title(&self) -> Option<String>;
+
+    // Fix up a deserialized item, returning a warning if it had to be
+    // repaired or an error if it is unusable
+    fn check(&mut self) -> Result<Option<String>, String> {
+        Ok(None)
+    }
+}
+
+// An element in the document, the name is its local name
+struct Element {
+    name: String,
+    span: Range<usize>,
+}
+
+// Outermost elements with the given local name, or with any name if None
+fn elements(bytes: &[u8], name: Option<&[u8]>) -> anyhow::Result<Vec<Element>> {
+    let mut reader = Reader::from_reader(bytes);
+    let mut buf = Vec::new();
+    let mut found = Vec::new();
+    let mut depth = 0usize;
+    let mut open: Option<(String, usize, usize)> = None;
+
+    loop {
+        let start = reader.buffer_position();
+        let event = reader.read_event_into(&mut buf)?;
+        let end = reader.buffer_position();
+        match event {
+            Event::Eof => break,
+            Event::Start(ref e) | Event::Empty(ref e) if open.is_none() => {
+                let matches = name.is_none_or(|name| e.local_name().as_ref() == name);
+                let local = String::from_utf8_lossy(e.local_name().as_ref()).to_string();
+                match (matches, matches!(event, Event::Empty(_))) {
+                    (true, true) => found.push(Element {
+                        name: local,
+                        span: start..end,
+                    }),
+                    (true, false) => open = Some((local, start, depth)),
+                    _ => (),
+                }
+                if matches!(event, Event::Start(_)) {
+                    depth += 1;
+                }
+            }
+            Event::Start(_) => depth += 1,
+            Event::End(_) => {
+                depth = depth.saturating_sub(1);
+                if let Some((ref local, start, open_depth)) = open {
+                    if depth == open_depth {
+                        found.push(Element {
+                            name: local.clone(),
+                            span: start..end,
+                        });
+                        open = None;
+                    }
+                }
+            }
+            _ => (),
+        }
+        buf.clear();
+    }
+
+    Ok(found)
+}
+
+fn without(bytes: &[u8], spans: &[Range<usize>]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut last = 0;
+    for span in spans {
+        out.extend_from_slice(&bytes[last..span.start]);
+        last = span.end;
+    }
+    out.extend_from_slice(&bytes[last..]);
+    out
+}
+
+fn raw_title(item: &[u8]) -> Option<String> {
+    lazy_static! {
+        static ref TITLE_REGEX: Regex =
+            Regex::new(r"(?s)<title\b[^>]*>(?:\s*<!\[CDATA\[)?(.*?)(?:\]\]>\s*)?</title>").unwrap();
+    }
+
+    let item = String::from_utf8_lossy(item);
+    let title = TITLE_REGEX.captures(&item)?.get(1)?.as_str();
+    Some(super::html::to_plain(title))
+}
+
+fn diagnostic(position: usize, title: Option<String>, message: String) -> Diagnostic {
+    Diagnostic {
+        item: position,
+        title: title
+            .filter(|t| !t.trim().is_empty())
+            .map(|t| t.trim().chars().take(80).collect()),
+        message,
+    }
+}
+
+// Run the item checks, dropping unusable items
+fn check_items<I: Item>(items: &mut Vec<I>, positions: &[usize]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut position = positions.iter();
+    items.retain_mut(|item| {
+        let position = position.next().copied().unwrap_or_default();
+        match item.check() {
+            Ok(None) => true,
+            Ok(Some(warning)) => {
+                diagnostics.push(diagnostic(position, item.title(), warning));
+                true
+            }
+            Err(e) => {
+                diagnostics.push(diagnostic(
+                    position,
+                    item.title(),
+                    format!("skipped: {}", e),
+                ));
+                false
+            }
+        }
+    });
+    diagnostics
+}
+
+// Deserialize a single item, dropping the first child element that keeps
+// it from deserializing if need be
+fn parse_item<I: Item>(position: usize, item: &[u8]) -> (Option<I>, Option<Diagnostic>) {
+    let err = match from_reader::<_, I>(item) {
+        Ok(parsed) => return (Some(parsed), None),
+        Err(e) => e,
+    };
+
+    // Children lie between the item's own start and end tags
+    let root = item.iter().position(|b| *b == b'>').map_or(0, |p| p + 1);
+    let end = item.iter().rposition(|b| *b == b'<').unwrap_or(item.len());
+    let children = elements(&item[root..end.max(root)], None).unwrap_or_default();
+    for child in children {
+        let span = child.span.start + root..child.span.end + root;
+        if let Ok(parsed) = from_reader::<_, I>(without(item, &[span]).as_slice()) {
+            let message = format!("ignored malformed <{}>: {}", child.name, err);
+            let title = parsed.title();
+            return (Some(parsed), Some(diagnostic(position, title, message)));
+        }
+    }
+
+    let message = format!("skipped: {}", err);
+    (None, Some(diagnostic(position, raw_title(item), message)))
+}
+
+// Deserialize a feed document. If that fails, deserialize the document
+// without its item elements, then each item on its own, so a broken item
+// only costs that item. Fails only when the document itself can't be read.
+pub fn from_xml<D: Document>(
+    bytes: &[u8],
+    item_name: &str,
+) -> anyhow::Result<(D, Vec<Diagnostic>)> {
+    let err = match from_reader::<_, D>(bytes) {
+        Ok(mut feed) => {
+            let positions = (1..=feed.items().len()).collect::<Vec<_>>();
+            let diagnostics = check_items(feed.items(), &positions);
+            return Ok((feed, diagnostics));
+        }
+        Err(e) => e,
+    };
+    debug!("Parsing items one at a time after error: {}", err);
+
+    let items = match elements(bytes, Some(item_name.as_bytes())) {
+        Ok(items) => items,
+        Err(_) => return Err(err.into()),
+    };
+    let spans = items.iter().map(|i| i.span.clone()).collect::<Vec<_>>();
+    let mut feed: D = from_reader(without(bytes, &spans).as_slice()).map_err(|_| err)?;
+
+    let mut diagnostics = Vec::new();
+    let mut positions = Vec::with_capacity(items.len());
+    for (idx, item) in items.iter().enumerate() {
+        let (item, diagnostic) = parse_item(idx + 1, &bytes[item.span.clone()]);
+        if let Some(item) = item {
+            feed.items().push(item);
+            positions.push(idx + 1);
+        }
+        diagnostics.extend(diagnostic);
+    }
+
+    diagnostics.extend(check_items(feed.items(), &positions));
+    diagnostics.sort_by_key(|d| d.item);
+    Ok((feed, diagnostics))
+}
+
+// Deserialize a JSON document the same way as from_xml, dropping the first
+// member that keeps an item from deserializing if need be
+pub fn from_json<D: Document>(
+    bytes: &[u8],
+    items_key: &str,
+) -> anyhow::Result<(D, Vec<Diagnostic>)> {
+    let err = match serde_json::from_slice::<D>(bytes) {
+        Ok(mut feed) => {
+            let positions = (1..=feed.items().len()).collect::<Vec<_>>();
+            let diagnostics = check_items(feed.items(), &positions);
+            return Ok((feed, diagnostics));
+        }
+        Err(e) => e,
+    };
+    debug!("Parsing items one at a time after error: {}", err);
+
+    let mut document: serde_json::Value = match serde_json::from_slice(bytes) {
+        Ok(document) => document,
+        Err(_) => return Err(err.into()),
+    };
+    let items = match document.get_mut(items_key).map(serde_json::Value::take) {
+        Some(serde_json::Value::Array(items)) => items,
+        _ => Vec::new(),
+    };
+    let mut feed: D = serde_json::from_value(document).map_err(|_| err)?;
+
+    let mut diagnostics = Vec::new();
+    let mut positions = Vec::with_capacity(items.len());
+    for (idx, item) in items.into_iter().enumerate() {
+        let title = item
+            .get("title")
+            .and_then(serde_json::Value::as_str)
+            .map(String::from);
+        let err = match serde_json::from_value::<D::Item>(item.clone()) {
+            Ok(parsed) => {
+                feed.items().push(parsed);
+                positions.push(idx + 1);
+                continue;
+            }
+            Err(e) => e,
+        };
+
+        let repaired = item.as_object().and_then(|members| {
+            members.keys().find_map(|key| {
+                let mut members = members.clone();
+                members.remove(key);
+                serde_json::from_value::<D::Item>(members.into())
+                    .ok()
+                    .map(|parsed| (key.clone(), parsed))
+            })
+        });
+        let message = match repaired {
+            Some((key, parsed)) => {
+                feed.items().push(parsed);
+                positions.push(idx + 1);
+                format!("ignored malformed \"{}\": {}", key, err)
+            }
+            None => format!("skipped: {}", err),
+        };
+        diagnostics.push(diagnostic(idx + 1, title, message));
+    }
+
+    diagnostics.extend(check_items(feed.items(), &positions));
+    diagnostics.sort_by_key(|d| d.item);
+    Ok((feed, diagnostics))
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use crate::feed::{atom, rss};
+
+    fn get_test_dir() -> PathBuf {
+        std::env::current_dir()
+            .expect("failed to get current directory")
+            .join("test")
+    }
+
+    #[test]
+    fn broken_rss_items() {
+        let file = std::fs::File::open(get_test_dir().join("broken_rss.xml")).unwrap();
+        let feed = rss::xml_from_reader("broken", std::io::BufReader::new(file))
+            .expect("lenient parsing should keep the good items");
+
+        let titles = feed
+            .channel
+            .item
+            .iter()
+            .map(|i| i.title.as_deref().unwrap_or_default())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            titles,
+            vec!["Good item", "Bad date", "No link", "Another good item"]
+        );
+        assert_eq!(feed.channel.item[1].date, None);
+        assert_eq!(feed.channel.item[2].link, "https://example.com/no-link");
+
+        let diagnostics = feed
+            .channel
+            .diagnostics
+            .iter()
+            .map(|d| (d.item, d.title.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            diagnostics,
+            vec![
+                (2, Some("Bad date")),
+                (3, Some("No link")),
+                (4, Some("Unusable")),
+            ]
+        );
+        assert!(feed.channel.diagnostics[0]
+            .message
+            .starts_with("ignored malformed <pubDate>"));
+    }
+
+    #[test]
+    fn broken_atom_entries() {
+        let file = std::fs::File::open(get_test_dir().join("broken_atom.xml")).unwrap();
+        let feed = atom::xml_from_reader("broken", std::io::BufReader::new(file))
+            .expect("lenient parsing should keep the good entries");
+
+        assert_eq!(feed.title, "Broken Atom");
+        assert_eq!(feed.entry.len(), 2);
+        assert_eq!(feed.entry[0].title, "Good entry");
+        assert_eq!(feed.entry[1].title, "Bad update");
+        assert_eq!(feed.entry[1].updated, None);
+        assert_eq!(feed.diagnostics.len(), 2);
+        assert_eq!(feed.diagnostics[1].item, 3);
+        assert!(feed.diagnostics[1].message.starts_with("skipped"));
+    }
+
+    #[test]
+    fn good_feeds_have_no_diagnostics() {
+        let file = std::fs::File::open(get_test_dir().join("rssboard.xml")).unwrap();
+        let feed = rss::xml_from_reader("good", std::io::BufReader::new(file)).unwrap();
+        assert!(feed.channel.diagnostics.is_empty());
+    }
+}
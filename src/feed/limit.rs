@@ -0,0 +1,244 @@
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Mutex, PoisonError},
+    time::Duration,
+};
+use tokio::{
+    sync::{Semaphore, SemaphorePermit},
+    time::{sleep_until, Instant},
+};
+
+use crate::CONFIG;
+
+// Window requests per minute are counted in
+const MINUTE: Duration = Duration::from_secs(60);
+
+lazy_static! {
+    // Recent and reserved request times by host, oldest first, kept between
+    // updates so a host polled by several feeds is spaced across them
+    static ref REQUESTS: Mutex<HashMap<String, Vec<Instant>>> = Mutex::new(HashMap::new());
+    // Fetch slots shared by background updates and commands, so together
+    // they stay under maxConcurrent
+    static ref FETCHES: Semaphore = match CONFIG.read() {
+        Err(_) => RateLimitConfig::default().semaphore(),
+        Ok(cfg) => cfg.rate_limit.semaphore(),
+    };
+}
+
+// How hard polls may hit servers, the rateLimit table of the config
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RateLimitConfig {
+    // Fetches running at once across every host, 0 for no limit
+    pub max_concurrent: usize,
+    // Requests to one host, 0 for no limit
+    pub requests_per_minute: u32,
+    // Seconds between requests to one host
+    pub min_spacing: u64,
+    // Limits for hosts, and their subdomains, that differ from the above
+    pub hosts: BTreeMap<String, HostLimit>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent: 8,
+            requests_per_minute: 30,
+            min_spacing: 1,
+            hosts: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HostLimit {
+    pub requests_per_minute: Option<u32>,
+    pub min_spacing: Option<u64>,
+}
+
+// The limit on requests to one host
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub struct Limit {
+    pub requests_per_minute: u32,
+    pub min_spacing: Duration,
+}
+
+impl RateLimitConfig {
+    // The limit for host, from the most specific entry of hosts naming it or
+    // a domain it is in
+    pub fn for_host(&self, host: &str) -> Limit {
+        let host = host.trim_end_matches('.').to_lowercase();
+        let entry = self
+            .hosts
+            .iter()
+            .filter(|(domain, _)| {
+                let domain = domain.trim_start_matches('.').to_lowercase();
+                host == domain || host.ends_with(&format!(".{}", domain))
+            })
+            .max_by_key(|(domain, _)| domain.trim_start_matches('.').len())
+            .map(|(_, limit)| limit.clone())
+            .unwrap_or_default();
+        Limit {
+            requests_per_minute: entry
+                .requests_per_minute
+                .unwrap_or(self.requests_per_minute),
+            min_spacing: Duration::from_secs(entry.min_spacing.unwrap_or(self.min_spacing)),
+        }
+    }
+
+    pub fn semaphore(&self) -> Semaphore {
+        match self.max_concurrent {
+            0 => Semaphore::new(Semaphore::MAX_PERMITS),
+            max => Semaphore::new(max),
+        }
+    }
+}
+
+// When the next request after times may go out, forgetting requests older
+// than the window
+fn ready_at(times: &mut Vec<Instant>, limit: Limit, now: Instant) -> Instant {
+    times.retain(|time| *time + MINUTE > now);
+    let mut ready = now;
+    if let Some(last) = times.last() {
+        ready = ready.max(*last + limit.min_spacing);
+    }
+    let rpm = limit.requests_per_minute as usize;
+    if rpm > 0 && times.len() >= rpm {
+        ready = ready.max(times[times.len() - rpm] + MINUTE);
+    }
+    ready
+}
+
+// Wait for a fetch slot and for host to allow another request, counting the
+// request against the configured limits. Every request counts, retries and
+// redirects included. The slot is held until the permit is dropped.
+pub async fn acquire(host: &str) -> Option<SemaphorePermit<'static>> {
+    let limit = match CONFIG.read() {
+        Err(_) => RateLimitConfig::default().for_host(host),
+        Ok(cfg) => cfg.rate_limit.for_host(host),
+    };
+    acquire_from(&FETCHES, host, limit).await
+}
+
+// Requests sent to host in the last minute
+#[cfg(test)]
+pub fn recent(host: &str) -> usize {
+    let mut requests = REQUESTS.lock().unwrap_or_else(PoisonError::into_inner);
+    let times = requests.entry(host.to_string()).or_default();
+    let now = Instant::now();
+    times.retain(|time| *time + MINUTE > now);
+    times.len()
+}
+
+// Wait for a slot of semaphore and for host to allow another request. The
+// slot is only taken once the host is ready, so a busy host doesn't hold slots
+// other hosts could use.
+async fn acquire_from<'a>(
+    semaphore: &'a Semaphore,
+    host: &str,
+    limit: Limit,
+) -> Option<SemaphorePermit<'a>> {
+    loop {
+        let ready = {
+            let mut requests = REQUESTS.lock().unwrap_or_else(PoisonError::into_inner);
+            ready_at(
+                requests.entry(host.to_string()).or_default(),
+                limit,
+                Instant::now(),
+            )
+        };
+        sleep_until(ready).await;
+
+        let permit = semaphore.acquire().await.ok()?;
+        let mut requests = REQUESTS.lock().unwrap_or_else(PoisonError::into_inner);
+        let times = requests.entry(host.to_string()).or_default();
+        let now = Instant::now();
+        if ready_at(times, limit, now) <= now {
+            times.push(now);
+            return Some(permit);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    use super::{ready_at, HostLimit, Limit, RateLimitConfig};
+
+    #[test]
+    fn host_limits() {
+        let mut config = RateLimitConfig::default();
+        config.hosts.insert(
+            "youtube.com".to_string(),
+            HostLimit {
+                requests_per_minute: Some(10),
+                min_spacing: Some(5),
+            },
+        );
+        config.hosts.insert(
+            "music.youtube.com".to_string(),
+            HostLimit {
+                requests_per_minute: Some(2),
+                ..Default::default()
+            },
+        );
+
+        let default = Limit {
+            requests_per_minute: 30,
+            min_spacing: Duration::from_secs(1),
+        };
+        assert_eq!(config.for_host("example.com"), default);
+        assert_eq!(config.for_host("notyoutube.com"), default);
+        let youtube = Limit {
+            requests_per_minute: 10,
+            min_spacing: Duration::from_secs(5),
+        };
+        assert_eq!(config.for_host("www.youtube.com"), youtube);
+        assert_eq!(config.for_host("YouTube.com."), youtube);
+        assert_eq!(
+            config.for_host("music.youtube.com"),
+            Limit {
+                requests_per_minute: 2,
+                min_spacing: Duration::from_secs(1),
+            }
+        );
+    }
+
+    #[test]
+    fn request_spacing() {
+        let start = Instant::now();
+        let limit = Limit {
+            requests_per_minute: 3,
+            min_spacing: Duration::from_secs(5),
+        };
+        let mut times = Vec::new();
+        assert_eq!(ready_at(&mut times, limit, start), start);
+        times.push(start);
+        assert_eq!(
+            ready_at(&mut times, limit, start),
+            start + Duration::from_secs(5)
+        );
+
+        times.push(start + Duration::from_secs(5));
+        times.push(start + Duration::from_secs(10));
+        let now = start + Duration::from_secs(15);
+        assert_eq!(
+            ready_at(&mut times, limit, now),
+            start + Duration::from_secs(60)
+        );
+
+        // Requests older than a minute no longer count
+        let later = start + Duration::from_secs(62);
+        assert_eq!(ready_at(&mut times, limit, later), later);
+        assert_eq!(times.len(), 2);
+
+        let unlimited = Limit::default();
+        let mut times = vec![start; 100];
+        assert_eq!(ready_at(&mut times, unlimited, start), start);
+    }
+}
@@ -0,0 +1,244 @@
+use serde::{Deserialize, Serialize};
+use serenity::builder::CreateEmbed;
+
+use super::text::Text;
+
+// Media RSS (https://www.rssboard.org/media-rss) elements of an item, either
+// from a media:group or collected from the item itself
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq, Default)]
+pub struct MediaGroup {
+    #[serde(rename = "media-content", default)]
+    pub content: Vec<MediaContent>,
+    #[serde(rename = "media-thumbnail", default)]
+    pub thumbnail: Vec<Thumbnail>,
+    #[serde(rename = "media-title")]
+    pub title: Option<String>,
+    #[serde(rename = "media-description")]
+    pub description: Option<Text>,
+    #[serde(rename = "media-community")]
+    pub community: Option<Community>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq, Default)]
+pub struct MediaContent {
+    #[serde(rename = "@url")]
+    pub url: Option<String>,
+    #[serde(rename = "@type")]
+    pub content_type: Option<String>,
+    #[serde(rename = "@medium")]
+    pub medium: Option<String>,
+    #[serde(rename = "@width")]
+    pub width: Option<u32>,
+    #[serde(rename = "@height")]
+    pub height: Option<u32>,
+    #[serde(rename = "@fileSize")]
+    pub file_size: Option<u64>,
+    #[serde(rename = "@duration")]
+    pub duration: Option<u64>,
+    #[serde(rename = "media-thumbnail", default)]
+    pub thumbnail: Vec<Thumbnail>,
+    #[serde(rename = "media-title")]
+    pub title: Option<String>,
+    #[serde(rename = "media-description")]
+    pub description: Option<Text>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq, Default)]
+pub struct Thumbnail {
+    #[serde(rename = "@url")]
+    pub url: String,
+    #[serde(rename = "@width")]
+    pub width: Option<u32>,
+    #[serde(rename = "@height")]
+    pub height: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq, Default)]
+pub struct Community {
+    #[serde(rename = "media-starRating")]
+    pub star_rating: Option<StarRating>,
+    #[serde(rename = "media-statistics")]
+    pub statistics: Option<Statistics>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq, Default)]
+pub struct StarRating {
+    #[serde(rename = "@average")]
+    pub average: Option<String>,
+    #[serde(rename = "@count")]
+    pub count: Option<u64>,
+    #[serde(rename = "@max")]
+    pub max: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq, Default)]
+pub struct Statistics {
+    #[serde(rename = "@views")]
+    pub views: Option<u64>,
+}
+
+fn largest(thumbs: &[Thumbnail]) -> Option<&str> {
+    thumbs
+        .iter()
+        .rev()
+        .max_by_key(|t| t.width.unwrap_or_default())
+        .map(|t| t.url.as_str())
+}
+
+impl MediaContent {
+    fn is_image(&self) -> bool {
+        self.medium.as_deref() == Some("image")
+            || self
+                .content_type
+                .as_ref()
+                .is_some_and(super::is_image_mime_type)
+    }
+}
+
+impl MediaGroup {
+    // Media elements placed directly on an item, merged into its group
+    pub fn merge(
+        group: Option<MediaGroup>,
+        content: Vec<MediaContent>,
+        thumbnail: Vec<Thumbnail>,
+        description: Option<Text>,
+    ) -> Option<MediaGroup> {
+        if content.is_empty() && thumbnail.is_empty() && description.is_none() {
+            return group;
+        }
+
+        let mut group = group.unwrap_or_default();
+        group.content.extend(content);
+        group.thumbnail.extend(thumbnail);
+        if group.description.is_none() {
+            group.description = description;
+        }
+        Some(group)
+    }
+
+    // Largest thumbnail, falling back to thumbnails of the content and image content
+    pub fn thumbnail(&self) -> Option<&str> {
+        largest(&self.thumbnail)
+            .or_else(|| self.content.iter().find_map(|c| largest(&c.thumbnail)))
+            .or_else(|| {
+                self.content
+                    .iter()
+                    .filter(|c| c.is_image())
+                    .find_map(|c| c.url.as_deref())
+            })
+    }
+
+    pub fn description(&self) -> Option<&Text> {
+        self.description
+            .as_ref()
+            .or_else(|| self.content.iter().find_map(|c| c.description.as_ref()))
+    }
+
+    // Address of the first playable (non image) media object
+    pub fn content_url(&self) -> Option<&str> {
+        self.content
+            .iter()
+            .filter(|c| !c.is_image())
+            // Flash players have not worked in years, e.g. those in YouTube feeds
+            .filter(|c| c.content_type.as_deref() != Some("application/x-shockwave-flash"))
+            .find_map(|c| c.url.as_deref())
+    }
+
+    pub fn views(&self) -> Option<u64> {
+        self.community
+            .as_ref()
+            .and_then(|c| c.statistics.as_ref())
+            .and_then(|s| s.views)
+    }
+
+    // Embed fields for whatever the description and image don't cover
+    pub fn add_fields(&self, embed: &mut CreateEmbed) {
+        if let Some(url) = self.content_url() {
+            embed.field("media", url, false);
+        }
+        if let Some(views) = self.views() {
+            embed.field("views", views, true);
+        }
+        if let Some(rating) = self.rating() {
+            embed.field("rating", rating, true);
+        }
+    }
+
+    // e.g. "4.80/5 (120 ratings)"
+    pub fn rating(&self) -> Option<String> {
+        let rating = self.community.as_ref()?.star_rating.as_ref()?;
+        let average = rating.average.as_ref()?;
+        let mut text = match rating.max {
+            Some(ref max) => format!("{}/{}", average, max),
+            None => average.clone(),
+        };
+        if let Some(count) = rating.count {
+            text.push_str(&format!(" ({} ratings)", count));
+        }
+        Some(text)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use crate::feed::text::TextType;
+
+    fn get_test_dir() -> PathBuf {
+        std::env::current_dir()
+            .expect("failed to get current directory")
+            .join("test")
+    }
+
+    #[test]
+    fn youtube_group() {
+        let file = std::fs::File::open(get_test_dir().join("youtube_channel.xml")).unwrap();
+        let feed = crate::feed::atom::xml_from_reader("youtube", std::io::BufReader::new(file))
+            .expect("failed to parse youtube feed");
+
+        let entry = &feed.entry[0];
+        assert_eq!(
+            entry.get_enclosure_img(),
+            Some("https://i3.ytimg.com/vi/6jPiuOXmxEc/hqdefault.jpg")
+        );
+
+        let media = entry.media.as_ref().expect("entry has no media:group");
+        assert_eq!(
+            media.title.as_deref(),
+            Some("What You Need To Learn 2023 (as a software engineer)")
+        );
+        assert!(media
+            .description()
+            .is_some_and(|d| d.value.starts_with("### Twitch")));
+        assert_eq!(media.views(), Some(93014));
+        assert_eq!(media.rating().as_deref(), Some("5.00/5 (5151 ratings)"));
+        assert_eq!(media.content_url(), None);
+    }
+
+    #[test]
+    fn loose_rss_elements() {
+        let file = std::fs::File::open(get_test_dir().join("media_rss.xml")).unwrap();
+        let feed = crate::feed::rss::xml_from_reader("media", std::io::BufReader::new(file))
+            .expect("failed to parse media rss feed");
+
+        let item = &feed.channel.item[0];
+        assert_eq!(item.title.as_deref(), Some("Launch day"));
+        assert_eq!(item.description, "Watch the <b>launch</b>.");
+        assert_eq!(
+            item.get_enclosure_img(),
+            Some("https://example.com/launch-large.jpg")
+        );
+
+        let media = item.media.as_ref().expect("item has no media elements");
+        assert_eq!(media.content_url(), Some("https://example.com/launch.mp4"));
+        assert_eq!(media.description().map(|d| d.kind), Some(TextType::Html));
+
+        // Media elements nested in an aliased prefix
+        let item = &feed.channel.item[1];
+        assert_eq!(
+            item.get_enclosure_img(),
+            Some("https://example.com/photo.jpg")
+        );
+    }
+}
@@ -0,0 +1,78 @@
+use quick_xml::{
+    events::{BytesEnd, BytesStart, Event},
+    name::{Namespace, ResolveResult},
+    NsReader, Writer,
+};
+
+// Extension namespaces whose elements would otherwise clash with the core
+// RSS and Atom elements, since the deserializer only looks at local names
+const NAMESPACES: &[(&str, &[u8])] = &[
+    ("media", b"http://search.yahoo.com/mrss/"),
+    ("itunes", b"http://www.itunes.com/dtds/podcast-1.0.dtd"),
+    ("podcast", b"https://podcastindex.org/namespace/1.0"),
+    ("sy", b"http://purl.org/rss/1.0/modules/syndication/"),
+];
+
+// Atom elements are only an extension inside RSS documents
+pub const ATOM: (&str, &[u8]) = ("atom", b"http://www.w3.org/2005/Atom");
+
+// Namespace names as they are written in the wild differ in scheme, case
+// and trailing slashes
+fn normalize_uri(uri: &[u8]) -> &[u8] {
+    let uri = uri
+        .strip_prefix(b"https://")
+        .or_else(|| uri.strip_prefix(b"http://"))
+        .unwrap_or(uri);
+    uri.strip_suffix(b"/").unwrap_or(uri)
+}
+
+fn qualified_name(ns: &ResolveResult, local: &[u8], extra: &[(&str, &[u8])]) -> Option<String> {
+    let ResolveResult::Bound(Namespace(uri)) = ns else {
+        return None;
+    };
+    let uri = normalize_uri(uri);
+
+    NAMESPACES.iter().chain(extra).find_map(|(prefix, known)| {
+        normalize_uri(known)
+            .eq_ignore_ascii_case(uri)
+            .then(|| format!("{}-{}", prefix, String::from_utf8_lossy(local)))
+    })
+}
+
+// Rename extension elements, and those of the extra namespaces, to
+// "{prefix}-{local name}" using the conventional prefix, whatever prefix the
+// document itself binds the namespace to
+pub fn qualify(bytes: &[u8], extra: &[(&str, &[u8])]) -> anyhow::Result<Vec<u8>> {
+    let mut reader = NsReader::from_reader(bytes);
+    let mut writer = Writer::new(Vec::with_capacity(bytes.len()));
+    let mut buf = Vec::new();
+
+    loop {
+        let (ns, event) = reader.read_resolved_event_into(&mut buf)?;
+        match event {
+            Event::Eof => break,
+            Event::Start(ref e) | Event::Empty(ref e) => {
+                match qualified_name(&ns, e.local_name().as_ref(), extra) {
+                    Some(name) => {
+                        let mut start = BytesStart::new(name);
+                        start.extend_attributes(e.attributes().flatten());
+                        if matches!(event, Event::Start(_)) {
+                            writer.write_event(Event::Start(start))?;
+                        } else {
+                            writer.write_event(Event::Empty(start))?;
+                        }
+                    }
+                    None => writer.write_event(event)?,
+                }
+            }
+            Event::End(ref e) => match qualified_name(&ns, e.local_name().as_ref(), extra) {
+                Some(name) => writer.write_event(Event::End(BytesEnd::new(name)))?,
+                None => writer.write_event(event)?,
+            },
+            e => writer.write_event(e)?,
+        }
+        buf.clear();
+    }
+
+    Ok(writer.into_inner())
+}
@@ -0,0 +1,251 @@
+use serde::{Deserialize, Serialize};
+use serenity::builder::CreateEmbed;
+
+use super::rss::Enclosure;
+
+// Podcast episode metadata from the iTunes
+// (https://help.apple.com/itc/podcasts_connect/#/itcb54353390) and
+// Podcasting 2.0 (https://podcastindex.org/namespace/1.0) namespaces
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq, Default)]
+pub struct Episode {
+    pub duration: Option<String>,
+    pub image: Option<String>,
+    pub episode: Option<String>,
+    pub season: Option<String>,
+    pub summary: Option<String>,
+    #[serde(default)]
+    pub transcript: Vec<Transcript>,
+    pub chapters: Option<Chapters>,
+}
+
+// itunes:image, the artwork is in the href attribute
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq, Default)]
+pub struct Image {
+    #[serde(rename = "@href")]
+    pub href: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq, Default)]
+pub struct Transcript {
+    #[serde(rename = "@url")]
+    pub url: String,
+    #[serde(rename = "@type")]
+    pub content_type: Option<String>,
+    #[serde(rename = "@language")]
+    pub language: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq, Default)]
+pub struct Chapters {
+    #[serde(rename = "@url")]
+    pub url: String,
+    #[serde(rename = "@type")]
+    pub content_type: Option<String>,
+}
+
+impl Episode {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    // Episode and season numbers, e.g. "S2 E5"
+    pub fn number(&self) -> Option<String> {
+        let episode = self.episode.as_deref().map(str::trim);
+        let season = self.season.as_deref().map(str::trim);
+        match (season, episode) {
+            (Some(s), Some(e)) => Some(format!("S{} E{}", s, e)),
+            (None, Some(e)) => Some(format!("E{}", e)),
+            (Some(s), None) => Some(format!("S{}", s)),
+            (None, None) => None,
+        }
+    }
+
+    pub fn add_fields(&self, embed: &mut CreateEmbed) {
+        if let Some(number) = self.number() {
+            embed.field("episode", number, true);
+        }
+
+        if let Some(duration) = self.duration.as_deref().and_then(format_duration) {
+            embed.field("duration", duration, true);
+        }
+
+        if let Some(transcript) = self.transcript.first() {
+            embed.field("transcript", &transcript.url, false);
+        }
+
+        if let Some(ref chapters) = self.chapters {
+            embed.field("chapters", &chapters.url, false);
+        }
+    }
+}
+
+pub fn is_playable_mime_type(mime: impl AsRef<str>) -> bool {
+    let mime = mime.as_ref().trim().to_lowercase();
+    mime.starts_with("audio/") || mime.starts_with("video/")
+}
+
+// e.g. "[Play](https://example.com/ep5.mp3) (12.3 MB)"
+pub fn play_link(enclosure: &Enclosure) -> String {
+    if enclosure.length > 0 {
+        format!(
+            "[Play]({}) ({})",
+            enclosure.url,
+            format_size(enclosure.length)
+        )
+    } else {
+        format!("[Play]({})", enclosure.url)
+    }
+}
+
+// itunes:duration is either a number of seconds, MM:SS or HH:MM:SS
+pub fn parse_duration(duration: &str) -> Option<u64> {
+    let duration = duration.trim();
+    if duration.is_empty() {
+        return None;
+    }
+
+    let parts = duration
+        .split(':')
+        .map(|part| {
+            part.trim()
+                .split('.')
+                .next()
+                .unwrap_or_default()
+                .parse::<u64>()
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .ok()?;
+    if parts.len() > 3 {
+        return None;
+    }
+
+    Some(parts.iter().fold(0, |total, part| total * 60 + part))
+}
+
+// e.g. "1:02:03" or "4:05"
+pub fn format_duration(duration: &str) -> Option<String> {
+    let seconds = parse_duration(duration)?;
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        Some(format!("{}:{:02}:{:02}", hours, minutes, seconds))
+    } else {
+        Some(format!("{}:{:02}", minutes, seconds))
+    }
+}
+
+// e.g. "12.3 MB"
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1000 {
+        return format!("{} B", bytes);
+    }
+
+    let mut size = bytes as f64;
+    let mut unit = "B";
+    for u in UNITS {
+        if size < 1000.0 {
+            break;
+        }
+        size /= 1000.0;
+        unit = u;
+    }
+    format!("{:.1} {}", size, unit)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs::File, io::BufReader, path::PathBuf};
+
+    use super::{format_duration, format_size, play_link, Chapters, Episode, Transcript};
+    use crate::feed::rss::{xml_from_reader, Enclosure};
+
+    fn get_test_dir() -> PathBuf {
+        std::env::current_dir()
+            .expect("failed to get current directory")
+            .join("test")
+    }
+
+    #[test]
+    fn durations() {
+        let cases = [
+            ("3600", Some("1:00:00")),
+            ("125", Some("2:05")),
+            ("04:05", Some("4:05")),
+            ("1:02:03", Some("1:02:03")),
+            ("62:03", Some("1:02:03")),
+            ("45.5", Some("0:45")),
+            ("", None),
+            ("about an hour", None),
+            ("1:2:3:4", None),
+        ];
+
+        for (duration, expected) in cases {
+            assert_eq!(
+                format_duration(duration).as_deref(),
+                expected,
+                "{}",
+                duration
+            );
+        }
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(12_345_678), "12.3 MB");
+        assert_eq!(format_size(2_000_000_000), "2.0 GB");
+    }
+
+    #[test]
+    fn full_file() {
+        let file = File::open(get_test_dir().join("podcast.xml")).unwrap();
+        let feed = xml_from_reader("podcast", BufReader::new(file)).unwrap();
+
+        let item = &feed.channel.item[0];
+        assert_eq!(
+            item.podcast,
+            Some(Episode {
+                duration: Some("00:42:17".to_owned()),
+                image: Some("https://example.com/ep5.jpg".to_owned()),
+                episode: Some("5".to_owned()),
+                season: Some("2".to_owned()),
+                summary: Some("We talk about feeds.".to_owned()),
+                transcript: vec![Transcript {
+                    url: "https://example.com/ep5.vtt".to_owned(),
+                    content_type: Some("text/vtt".to_owned()),
+                    language: Some("en".to_owned()),
+                }],
+                chapters: Some(Chapters {
+                    url: "https://example.com/ep5.json".to_owned(),
+                    content_type: Some("application/json+chapters".to_owned()),
+                }),
+            })
+        );
+        assert_eq!(
+            item.podcast.as_ref().unwrap().number().as_deref(),
+            Some("S2 E5")
+        );
+        assert_eq!(
+            play_link(item.enclosure.as_ref().unwrap()),
+            "[Play](https://example.com/ep5.mp3) (24.6 MB)"
+        );
+
+        // Episodes without their own artwork fall back to the show's
+        let item = &feed.channel.item[1];
+        let episode = item.podcast.as_ref().unwrap();
+        assert_eq!(
+            episode.image.as_deref(),
+            Some("https://example.com/show.jpg")
+        );
+        assert_eq!(episode.number(), Some("E4".to_owned()));
+
+        assert_eq!(
+            play_link(&Enclosure {
+                url: "https://example.com/a.mp3".to_owned(),
+                length: 0,
+                content_type: "audio/mpeg".to_owned(),
+            }),
+            "[Play](https://example.com/a.mp3)"
+        );
+    }
+}
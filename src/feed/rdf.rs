@@ -0,0 +1,216 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::BufRead;
+
+use super::date;
+use super::lenient::{self, Diagnostic};
+use super::rss::{RssChannel, RssFeed, RssItem};
+
+// RSS 1.0 (RDF Site Summary) file, items are siblings of the channel
+// https://web.resource.org/rss/1.0/spec
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq, Default)]
+pub struct RdfFeed {
+    pub channel: RdfChannel,
+    pub image: Option<RdfImage>,
+    #[serde(default)]
+    pub item: Vec<RdfItem>,
+    #[serde(skip)]
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq, Default)]
+pub struct RdfChannel {
+    pub title: String,
+    #[serde(default)]
+    pub link: String,
+    #[serde(default)]
+    pub description: String,
+    // dc:date
+    #[serde(deserialize_with = "date::deserialize", default)]
+    pub date: Option<DateTime<Utc>>,
+    // dc:creator
+    pub creator: Option<String>,
+    // dc:rights
+    pub rights: Option<String>,
+    // RSS syndication module
+    #[serde(rename = "sy-updatePeriod", default)]
+    pub sy_update_period: Option<String>,
+    #[serde(rename = "sy-updateFrequency", default)]
+    pub sy_update_frequency: Option<String>,
+    #[serde(rename = "sy-updateBase", default)]
+    pub sy_update_base: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq, Default)]
+pub struct RdfImage {
+    pub url: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq, Default)]
+pub struct RdfItem {
+    #[serde(rename = "@about")]
+    pub about: Option<String>,
+    pub title: Option<String>,
+    pub link: String,
+    #[serde(default)]
+    pub description: String,
+    // dc:date
+    #[serde(deserialize_with = "date::deserialize", default)]
+    pub date: Option<DateTime<Utc>>,
+    // dc:creator
+    pub creator: Option<String>,
+}
+
+pub fn xml_from_reader(url: impl Into<String>, mut read: impl BufRead) -> anyhow::Result<RdfFeed> {
+    let mut bytes = Vec::new();
+    read.read_to_end(&mut bytes)?;
+
+    let (mut feed, diagnostics) = lenient::from_xml::<RdfFeed>(&bytes, "item")
+        .map_err(|e| anyhow::anyhow!("{}: {}", url.into(), e))?;
+    feed.diagnostics = diagnostics;
+    Ok(feed)
+}
+
+impl lenient::Document for RdfFeed {
+    type Item = RdfItem;
+
+    fn items(&mut self) -> &mut Vec<RdfItem> {
+        &mut self.item
+    }
+}
+
+impl lenient::Item for RdfItem {
+    fn title(&self) -> Option<String> {
+        self.title.clone()
+    }
+}
+
+pub fn is_rdf(bytes: &[u8]) -> bool {
+    super::root_element(bytes).is_some_and(|root| root == "RDF")
+}
+
+impl From<RdfItem> for RssItem {
+    fn from(item: RdfItem) -> Self {
+        Self {
+            title: item.title,
+            guid: item.about.or_else(|| Some(item.link.clone())),
+            link: item.link,
+            description: item.description,
+            date: item.date,
+            author: item.creator,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<RdfFeed> for RssFeed {
+    fn from(rdf: RdfFeed) -> Self {
+        Self {
+            channel: RssChannel {
+                title: rdf.channel.title,
+                description: rdf.channel.description,
+                link: rdf.channel.link,
+                pub_date: rdf.channel.date,
+                managing_editor: rdf.channel.creator,
+                copyright: rdf.channel.rights,
+                sy_update_period: rdf.channel.sy_update_period,
+                sy_update_frequency: rdf.channel.sy_update_frequency,
+                sy_update_base: rdf.channel.sy_update_base,
+                image: rdf.image.map(|img| img.url),
+                item: rdf.item.into_iter().map(Into::into).collect(),
+                diagnostics: rdf.diagnostics,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{DateTime, FixedOffset, NaiveDate};
+    use std::path::PathBuf;
+    use tokio::runtime;
+
+    use crate::feed::rss::{RssChannel, RssFeed, RssItem};
+    use crate::feed::Feed;
+
+    fn get_test_dir() -> PathBuf {
+        std::env::current_dir()
+            .expect("failed to get current directory")
+            .join("test")
+    }
+
+    #[test]
+    fn sniff_root() {
+        let rdf = std::fs::read(get_test_dir().join("rdf.xml")).unwrap();
+        let rss = std::fs::read(get_test_dir().join("rssboard.xml")).unwrap();
+        assert!(super::is_rdf(&rdf));
+        assert!(!super::is_rdf(&rss));
+    }
+
+    #[test]
+    fn full_file() {
+        runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let url = get_test_dir().join("rdf.xml");
+                let feed = crate::feed::from_url(format!("file://{}", url.to_string_lossy()), None, None, &Default::default())
+                .await;
+                assert!(feed.is_ok());
+
+                let feed = feed.unwrap();
+                let expected_feed = RssFeed {
+                    channel: RssChannel {
+                        title: "XML.com".to_owned(),
+                        link: "http://xml.com/pub".to_owned(),
+                        url: format!("file://{}", url.to_string_lossy()),
+                        description: "XML.com features a rich mix of information and services for the XML community.".to_owned(),
+                        pub_date: Some(
+                            DateTime::<FixedOffset>::parse_from_rfc3339("2000-08-09T12:00:00Z")
+                                .unwrap()
+                                .into(),
+                        ),
+                        copyright: Some("Copyright 2000 O'Reilly & Associates".to_owned()),
+                        sy_update_period: Some("daily".to_owned()),
+                        sy_update_frequency: Some("4".to_owned()),
+                        image: Some("http://xml.com/universal/images/xml_tiny.gif".to_owned()),
+                        item: vec![
+                            RssItem {
+                                title: Some("Processing Inclusions with XSLT".to_owned()),
+                                link: "http://xml.com/pub/2000/08/09/xslt/xslt.html".to_owned(),
+                                description: "Processing document inclusions with general XML tools can be problematic. This article proposes a way of preserving inclusion information through SAX-based processing.".to_owned(),
+                                date: Some(
+                                    DateTime::<FixedOffset>::parse_from_rfc3339(
+                                        "2000-08-09T08:30:00-05:00",
+                                    )
+                                    .unwrap()
+                                    .into(),
+                                ),
+                                author: Some("Bob DuCharme".to_owned()),
+                                guid: Some("http://xml.com/pub/2000/08/09/xslt/xslt.html".to_owned()),
+                                ..Default::default()
+                            },
+                            RssItem {
+                                title: Some("Putting RDF to Work".to_owned()),
+                                link: "http://xml.com/pub/2000/08/09/rdfdb/index.html".to_owned(),
+                                description: "Tool and API support for the Resource Description Framework is slowly coming of age. Edd Dumbill takes a look at RDFDB, one of the most exciting new RDF toolkits.".to_owned(),
+                                date: Some(
+                                    NaiveDate::from_ymd_opt(2000, 8, 9)
+                                        .unwrap()
+                                        .and_hms_opt(0, 0, 0)
+                                        .unwrap()
+                                        .and_utc(),
+                                ),
+                                author: Some("Edd Dumbill".to_owned()),
+                                guid: Some("http://xml.com/pub/2000/08/09/rdfdb/index.html".to_owned()),
+                                ..Default::default()
+                            },
+                        ],
+                        ..Default::default()
+                    },
+                };
+                assert_eq!(Feed::Rss(expected_feed), feed);
+            });
+    }
+}
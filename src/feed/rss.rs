@@ -1,13 +1,20 @@
-use chrono::{DateTime, Datelike, Duration, Timelike, Utc, Weekday};
-use quick_xml::de::from_reader;
-use reqwest::{self, Url};
+use chrono::{DateTime, Datelike, Timelike, Utc, Weekday};
 use serde::{Deserialize, Serialize};
 use serenity::builder::CreateEmbed;
-use std::{
-    fs::File,
-    io::{BufRead, BufReader},
-};
-use tracing::{debug, info_span, instrument, Instrument};
+use std::io::BufRead;
+use tracing::{debug, instrument};
+
+use super::atom::Link;
+use super::date;
+use super::fetch::{RequestOptions, Validators};
+use super::health::Health;
+use super::html;
+use super::lenient::{self, Diagnostic};
+use super::media::{MediaContent, MediaGroup, Thumbnail};
+use super::podcast::{self, Episode};
+use super::schedule::Schedule;
+use super::text::Text;
+use super::websub::Subscription;
 
 // RSS Feed file
 #[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq, Default)]
@@ -33,21 +40,53 @@ pub struct RssChannel {
     #[serde(default)]
     pub category: Vec<Category>,
     pub docs: Option<String>,
+    #[serde(deserialize_with = "deserialize_ttl", default)]
     pub ttl: Option<usize>,
+    // RSS syndication module, see Feed::syndication
+    #[serde(rename = "sy-updatePeriod", default)]
+    pub sy_update_period: Option<String>,
+    #[serde(rename = "sy-updateFrequency", default)]
+    pub sy_update_frequency: Option<String>,
+    #[serde(rename = "sy-updateBase", default)]
+    pub sy_update_base: Option<String>,
     pub image: Option<String>,
+    // Show artwork, the fallback for episodes without their own
+    #[serde(rename = "itunes-image", default, skip_serializing)]
+    pub itunes_image: Option<podcast::Image>,
+    // Where the feed says it lives, see Feed::canonical_url
+    #[serde(rename = "atom-link", default, skip_serializing)]
+    pub atom_link: Vec<Link>,
+    #[serde(rename = "itunes-new-feed-url", default, skip_serializing)]
+    pub itunes_new_feed_url: Option<String>,
     #[serde(rename = "skipHours", default)]
-    pub skip_hours: Vec<Hour>,
+    pub skip_hours: SkipHours,
     #[serde(rename = "skipDays", default)]
-    pub skip_days: Vec<Day>,
+    pub skip_days: SkipDays,
     #[serde(default)]
     pub item: Vec<RssItem>,
     pub last_updated: Option<DateTime<Utc>>,
     pub discord_category: Option<String>,
+    // Items skipped or repaired the last time the feed was parsed
+    #[serde(default)]
+    pub diagnostics: Vec<Diagnostic>,
+    // ETag and Last-Modified of the last full response, for conditional requests
+    #[serde(default)]
+    pub validators: Validators,
+    #[serde(default)]
+    pub health: Health,
+    // Credentials and headers for the feed's requests
+    #[serde(default)]
+    pub request: RequestOptions,
+    #[serde(default)]
+    pub websub: Option<Subscription>,
+    #[serde(default)]
+    pub schedule: Schedule,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq, Default)]
 pub struct RssItem {
     pub title: Option<String>,
+    #[serde(default)]
     pub link: String,
     pub description: String,
     #[serde(
@@ -65,29 +104,136 @@ pub struct RssItem {
     pub guid: Option<String>,
     pub source: Option<Source>,
     pub read: Option<()>,
+    // dc:creator, folded into author when parsing
+    #[serde(rename = "creator", default, skip_serializing)]
+    pub dc_creator: Option<String>,
+    // dc:date, folded into date when parsing
+    #[serde(
+        rename = "date",
+        deserialize_with = "date::deserialize",
+        default,
+        skip_serializing
+    )]
+    pub dc_date: Option<DateTime<Utc>>,
+    #[serde(rename = "media-group")]
+    pub media: Option<MediaGroup>,
+    // media:content, media:thumbnail and media:description outside of a
+    // group, folded into media when parsing
+    #[serde(rename = "media-content", default, skip_serializing)]
+    pub media_content: Vec<MediaContent>,
+    #[serde(rename = "media-thumbnail", default, skip_serializing)]
+    pub media_thumbnail: Vec<Thumbnail>,
+    #[serde(rename = "media-description", default, skip_serializing)]
+    pub media_description: Option<Text>,
+    pub podcast: Option<Episode>,
+    // iTunes and Podcasting 2.0 elements, folded into podcast when parsing
+    #[serde(rename = "itunes-duration", default, skip_serializing)]
+    pub itunes_duration: Option<String>,
+    #[serde(rename = "itunes-image", default, skip_serializing)]
+    pub itunes_image: Option<podcast::Image>,
+    #[serde(rename = "itunes-episode", default, skip_serializing)]
+    pub itunes_episode: Option<String>,
+    #[serde(rename = "itunes-season", default, skip_serializing)]
+    pub itunes_season: Option<String>,
+    #[serde(rename = "itunes-summary", default, skip_serializing)]
+    pub itunes_summary: Option<String>,
+    #[serde(rename = "podcast-transcript", default, skip_serializing)]
+    pub podcast_transcript: Vec<podcast::Transcript>,
+    #[serde(rename = "podcast-chapters", default, skip_serializing)]
+    pub podcast_chapters: Option<podcast::Chapters>,
 }
 
 impl RssItem {
+    // Fill in missing RSS 2.0 elements from their Dublin Core equivalents
+    fn apply_dublin_core(&mut self) {
+        if let Some(creator) = self.dc_creator.take() {
+            self.author.get_or_insert(creator);
+        }
+        if let Some(date) = self.dc_date.take() {
+            self.date.get_or_insert(date);
+        }
+    }
+
+    fn apply_media(&mut self) {
+        self.media = MediaGroup::merge(
+            self.media.take(),
+            std::mem::take(&mut self.media_content),
+            std::mem::take(&mut self.media_thumbnail),
+            self.media_description.take(),
+        );
+    }
+
+    fn apply_podcast(&mut self, artwork: Option<&str>) {
+        let mut episode = Episode {
+            duration: self.itunes_duration.take(),
+            image: self.itunes_image.take().map(|img| img.href),
+            episode: self.itunes_episode.take(),
+            season: self.itunes_season.take(),
+            summary: self.itunes_summary.take(),
+            transcript: std::mem::take(&mut self.podcast_transcript),
+            chapters: self.podcast_chapters.take(),
+        };
+
+        if !episode.is_empty() {
+            if episode.image.is_none() {
+                episode.image = artwork.map(String::from);
+            }
+            self.podcast = Some(episode);
+        }
+    }
+
+    pub fn get_enclosure_img(&self) -> Option<&str> {
+        if let Some(ref enc) = self.enclosure {
+            if super::is_image_mime_type(&enc.content_type) {
+                return Some(&enc.url);
+            }
+        }
+
+        self.media.as_ref().and_then(MediaGroup::thumbnail)
+    }
+
     #[instrument(level = "debug")]
     pub fn to_embed(&self) -> impl Fn(&mut CreateEmbed) -> &mut CreateEmbed {
         let author = self.author.clone();
-        let description = self.description.clone();
+        let media_description = self.media.as_ref().and_then(MediaGroup::description);
+        let summary = self.podcast.as_ref().and_then(|p| p.summary.as_deref());
+        let description = match (media_description, summary) {
+            _ if !self.description.trim().is_empty() => {
+                html::to_markdown(&self.description, Some(&self.link))
+            }
+            (Some(media), _) => media.to_markdown(Some(&self.link)),
+            (None, Some(summary)) => html::to_markdown(summary, Some(&self.link)),
+            (None, None) => html::Markdown::default(),
+        };
         let title = self.title.clone();
-        let enclosure = self.enclosure.clone();
+        let image = self
+            .get_enclosure_img()
+            .map(String::from)
+            .or(description.image);
+        let description = html::limit_description(description.text);
         let date = self.date;
         let link = self.link.clone();
         let comments = self.comments.clone();
         let source = self.source.clone();
+        let media = self.media.clone();
+        let episode = self.podcast.clone();
+        let play = self
+            .enclosure
+            .as_ref()
+            .filter(|enc| podcast::is_playable_mime_type(&enc.content_type))
+            .map(podcast::play_link);
 
         move |embed: &mut CreateEmbed| {
             if let Some(ref a) = author {
                 embed.author(|author| author.name(a));
             }
 
-            if let Some(ref enc) = enclosure {
-                if super::is_image_mime_type(&enc.content_type) {
-                    embed.image(&enc.url);
-                }
+            if let Some(ref img) = image {
+                embed.image(img);
+            }
+
+            if let Some(artwork) = episode.as_ref().and_then(|e| e.image.as_ref()) {
+                embed.thumbnail(artwork);
             }
 
             if let Some(ref t) = title {
@@ -112,6 +258,18 @@ impl RssItem {
                 embed.field("source url", &s.url, true);
             }
 
+            if let Some(ref m) = media {
+                m.add_fields(embed);
+            }
+
+            if let Some(ref e) = episode {
+                e.add_fields(embed);
+            }
+
+            if let Some(ref p) = play {
+                embed.field("play", p, false);
+            }
+
             embed
         }
     }
@@ -125,56 +283,108 @@ pub struct Category {
     value: Option<String>,
 }
 
+// <skipHours>, the hours in GMT the feed shouldn't be read in, each in an
+// <hour> from 0 to 23
 #[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq, Default)]
-pub struct Hour {
-    #[serde(rename = "$text")]
-    hour: u8,
+pub struct SkipHours {
+    #[serde(default)]
+    pub hour: Vec<String>,
 }
 
-impl From<u32> for Hour {
-    fn from(u: u32) -> Self {
-        Self {
-            hour: (u % 24) as u8,
-        }
+impl SkipHours {
+    // Hours that aren't numbers are ignored, 24 is midnight as in RSS 0.91
+    pub fn hours(&self) -> impl Iterator<Item = u32> + '_ {
+        self.hour
+            .iter()
+            .filter_map(|hour| hour.trim().parse::<u32>().ok())
+            .filter(|hour| *hour <= 24)
+            .map(|hour| hour % 24)
     }
-}
 
-#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq, Default)]
-pub struct Day {
-    #[serde(rename = "$text")]
-    day: DaysOfWeek,
+    pub fn contains(&self, hour: u32) -> bool {
+        self.hours().any(|skipped| skipped == hour)
+    }
 }
 
+// <skipDays>, the days in GMT the feed shouldn't be read on, each in a <day>
+// from Monday to Sunday
 #[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq, Default)]
-pub enum DaysOfWeek {
-    #[default]
-    Monday,
-    Tuesday,
-    Wednesday,
-    Thursday,
-    Friday,
-    Saturday,
-    Sunday,
+pub struct SkipDays {
+    #[serde(default)]
+    pub day: Vec<String>,
 }
 
-impl From<Weekday> for Day {
-    fn from(day: Weekday) -> Self {
-        Self { day: day.into() }
+impl SkipDays {
+    // Days are matched without regard to case, unknown ones are ignored
+    pub fn days(&self) -> impl Iterator<Item = Weekday> + '_ {
+        self.day.iter().filter_map(|day| day.trim().parse().ok())
     }
+
+    pub fn contains(&self, day: Weekday) -> bool {
+        self.days().any(|skipped| skipped == day)
+    }
+}
+
+// Whether now falls in the skipped hours or days
+pub fn is_skipped(hours: &SkipHours, days: &SkipDays, now: DateTime<Utc>) -> bool {
+    days.contains(now.weekday()) || hours.contains(now.hour())
 }
 
-impl From<Weekday> for DaysOfWeek {
-    fn from(day: Weekday) -> Self {
-        match day {
-            Weekday::Mon => DaysOfWeek::Monday,
-            Weekday::Tue => DaysOfWeek::Tuesday,
-            Weekday::Wed => DaysOfWeek::Wednesday,
-            Weekday::Thu => DaysOfWeek::Thursday,
-            Weekday::Fri => DaysOfWeek::Friday,
-            Weekday::Sat => DaysOfWeek::Saturday,
-            Weekday::Sun => DaysOfWeek::Sunday,
+// <ttl> in minutes, ignored unless it is a whole number. Accepts both the XML
+// element and the number stored in the database.
+pub fn deserialize_ttl<'de, D>(deserializer: D) -> Result<Option<usize>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::de;
+
+    struct TtlVisitor;
+
+    impl<'de> de::Visitor<'de> for TtlVisitor {
+        type Value = Option<usize>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "a time to live in minutes")
+        }
+
+        fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+            Ok(None)
+        }
+
+        fn visit_some<D: serde::Deserializer<'de>>(
+            self,
+            deserializer: D,
+        ) -> Result<Self::Value, D::Error> {
+            deserializer.deserialize_any(TtlVisitor)
+        }
+
+        fn visit_u64<E: de::Error>(self, value: u64) -> Result<Self::Value, E> {
+            Ok(usize::try_from(value).ok())
+        }
+
+        fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+            Ok(value.trim().parse().ok())
+        }
+
+        fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut ttl = None;
+            while let Some(key) = map.next_key::<String>()? {
+                match key.as_str() {
+                    "$value" | "$text" => ttl = map.next_value::<String>()?.trim().parse().ok(),
+                    _ => {
+                        map.next_value::<de::IgnoredAny>()?;
+                    }
+                }
+            }
+            Ok(ttl)
         }
     }
+
+    deserializer.deserialize_option(TtlVisitor)
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq, Default)]
@@ -195,8 +405,61 @@ pub struct Source {
     source: String,
 }
 
-pub fn xml_from_reader(url: impl Into<String>, read: impl BufRead) -> anyhow::Result<RssFeed> {
-    from_reader(read).map_err(|e| anyhow::anyhow!("{}: {}", url.into(), e))
+// The document must already be UTF-8, see charset::to_utf8
+pub fn xml_from_reader(url: impl Into<String>, mut read: impl BufRead) -> anyhow::Result<RssFeed> {
+    let url = url.into();
+    let mut bytes = Vec::new();
+    read.read_to_end(&mut bytes)?;
+
+    // Malformed documents are left for the deserializer to report
+    let bytes = super::doctype::strip(&bytes).unwrap_or(bytes);
+    let bytes = super::namespace::qualify(&bytes, &[super::namespace::ATOM]).unwrap_or(bytes);
+    let mut feed: RssFeed = if super::rdf::is_rdf(&bytes) {
+        debug!("{} is an RSS 1.0 (RDF) document.", url);
+        super::rdf::xml_from_reader(&url, bytes.as_slice())?.into()
+    } else {
+        let (mut feed, diagnostics) = lenient::from_xml::<RssFeed>(&bytes, "item")
+            .map_err(|e| anyhow::anyhow!("{}: {}", url, e))?;
+        feed.channel.diagnostics = diagnostics;
+        feed
+    };
+
+    let artwork = feed.channel.itunes_image.take().map(|img| img.href);
+    feed.channel.item.iter_mut().for_each(|item| {
+        item.apply_dublin_core();
+        item.apply_media();
+        item.apply_podcast(artwork.as_deref());
+    });
+    Ok(feed)
+}
+
+impl lenient::Document for RssFeed {
+    type Item = RssItem;
+
+    fn items(&mut self) -> &mut Vec<RssItem> {
+        &mut self.channel.item
+    }
+}
+
+impl lenient::Item for RssItem {
+    fn title(&self) -> Option<String> {
+        self.title.clone()
+    }
+
+    // Items are told apart by their link, fall back on a permalink guid
+    fn check(&mut self) -> Result<Option<String>, String> {
+        if !self.link.trim().is_empty() {
+            return Ok(None);
+        }
+
+        match self.guid {
+            Some(ref guid) if guid.starts_with("http://") || guid.starts_with("https://") => {
+                self.link = guid.clone();
+                Ok(Some("no <link>, using <guid> instead".to_owned()))
+            }
+            _ => Err("no <link> or permalink <guid>".to_owned()),
+        }
+    }
 }
 
 pub mod rfc822 {
@@ -204,16 +467,11 @@ pub mod rfc822 {
     use serde::{self, Deserialize, Deserializer, Serializer};
 
     const FORMAT: &str = "%a, %d %b %Y %H:%M:%S %z";
-    const FORMAT_SHORT: &str = "%a, %d %b %Y %H:%M %z";
 
-    pub fn into_datetime(str: impl AsRef<str>) -> Result<DateTime<Utc>, chrono::ParseError> {
-        let parsed_long = DateTime::parse_from_str(str.as_ref(), FORMAT);
-        if parsed_long.is_ok() {
-            parsed_long.map(Into::into)
-        } else {
-            let next = DateTime::parse_from_str(str.as_ref(), FORMAT_SHORT).map(Into::into);
-            next
-        }
+    // Dates are written in RFC 822 format but read with the tolerant parser,
+    // since few feeds get them exactly right
+    pub fn into_datetime(str: impl AsRef<str>) -> anyhow::Result<DateTime<Utc>> {
+        crate::feed::date::parse(str)
     }
 
     pub fn serialize<S>(date: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error>
@@ -243,93 +501,32 @@ pub mod rfc822 {
 }
 
 impl RssFeed {
-    // Create a feed item from a URL to an RSS feed,
-    // Filling the title and category fields if given
-    // (title defaults to title from rss feed)
-    #[instrument(level = "debug", skip(url, user_agent))]
-    pub async fn from_url(
-        url: impl AsRef<str>,
-        user_agent: Option<impl AsRef<str>>,
-    ) -> anyhow::Result<Self> {
-        let url: Url = Url::parse(url.as_ref())?;
-
-        // Stream rss feed to the write end of the pipe in a new task
-        let mut feed = if url.scheme() == "http" || url.scheme() == "https" {
-            let bytes = (async {
-                let client = if let Some(user) = user_agent {
-                    reqwest::ClientBuilder::new()
-                        .user_agent(user.as_ref())
-                        .build()?
-                } else {
-                    reqwest::ClientBuilder::new().build()?
-                };
-                let req = client.get(url.clone()).build()?;
-                client.execute(req).await?.bytes().await
-            })
-            .instrument(info_span!("RssFeed::reqwest"))
-            .await?;
-
-            xml_from_reader(url.as_str(), BufReader::new(bytes.as_ref()))
-        } else if url.scheme() == "file" {
-            let _ = info_span!("RssFeed::File");
-            let path = url.path();
-            let file = File::open(path)?;
-            xml_from_reader(url.as_str(), BufReader::new(file))
-        } else {
-            anyhow::bail!("{}: unsupported url schema", url)
-        }?;
-
-        feed.channel.url = url.to_string();
-        Ok(feed)
-    }
-
-    // Use metadata on channel to figure out if it's time to update
+    // Whether the channel asks to be skipped right now, the ttl and sy: update
+    // period are part of the feed's interval (see Feed::next_poll)
     #[instrument(level = "trace")]
-    pub fn should_update(&self) -> bool {
-        if let Some(last_update) = self.channel.last_updated {
-            let now = chrono::offset::Utc::now();
-            if self
-                .channel
-                .skip_days
-                .contains(&now.date_naive().weekday().into())
-            {
-                debug!("Feed {} should be skipped today.", self.channel.title);
-                return false;
-            }
-            if self.channel.skip_hours.contains(&now.time().hour().into()) {
-                debug!("Feed {} should be skipped this hour.", self.channel.title);
-                return false;
-            }
+    pub fn should_update(&self, now: DateTime<Utc>) -> bool {
+        if self.channel.last_updated.is_none() {
+            return true;
+        }
 
-            if let Some(ttl) = self.channel.ttl {
-                debug!(
-                    "Checking ttl to see if {} should be updated now.",
-                    self.channel.title
-                );
-                let duration_since = now.signed_duration_since(last_update);
-                match Duration::from_std(std::time::Duration::from_secs(ttl as u64 * 60)) {
-                    Ok(ttl) => duration_since >= ttl,
-                    _ => true,
-                }
-            } else {
-                true
-            }
-        } else {
-            true
+        if is_skipped(&self.channel.skip_hours, &self.channel.skip_days, now) {
+            debug!("Feed {} should be skipped now.", self.channel.title);
+            return false;
         }
+        true
     }
 }
 
 #[cfg(test)]
 mod test {
-    use std::path::PathBuf;
+    use std::{fs::File, io::BufReader, path::PathBuf};
 
     use tokio::runtime;
 
     use super::rfc822::into_datetime;
 
-    #[allow(unused_imports)]
     use super::*;
+    use crate::feed::Feed;
 
     fn get_test_dir() -> PathBuf {
         std::env::current_dir()
@@ -344,11 +541,8 @@ mod test {
             .unwrap()
             .block_on(async {
                 let url = get_test_dir().join("empty.xml");
-                let feed = RssFeed::from_url(
-                    format!("file://{}", url.to_string_lossy()),
-                    Option::<String>::None,
-                )
-                .await;
+                let file = File::open(&url).unwrap();
+                let feed = xml_from_reader(url.to_string_lossy(), BufReader::new(file));
                 assert!(feed.is_err());
             });
     }
@@ -360,10 +554,7 @@ mod test {
             .unwrap()
             .block_on(async {
                 let url = get_test_dir().join("rssboard.xml");
-                let feed = RssFeed::from_url(
-                    format!("file://{}", url.to_string_lossy()),
-                    Option::<String>::None,
-                ).await;
+                let feed = crate::feed::from_url(format!("file://{}", url.to_string_lossy()), None, None, &Default::default()).await;
                 assert!(feed.is_ok());
                 let feed = feed.unwrap();
 
@@ -377,6 +568,12 @@ mod test {
                         docs: Some("https://www.rssboard.org/rss-specification".to_owned()),
                         managing_editor: Some("neil.armstrong@example.com (Neil Armstrong)".to_owned()),
                         web_master: Some("sally.ride@example.com (Sally Ride)".to_owned()),
+                        atom_link: vec![Link {
+                            href: "https://www.rssboard.org/files/sample-rss-2.xml".to_owned(),
+                            rel: Some("self".to_owned()),
+                            content_type: Some("application/rss+xml".to_owned()),
+                            ..Default::default()
+                        }],
                         item: vec![
                             RssItem {
                                 title: Some("Louisiana Students to Hear from NASA Astronauts Aboard Space Station".to_owned()),
@@ -432,7 +629,61 @@ mod test {
                         ..Default::default()
                     },
                 };
-                assert_eq!(expected_feed, feed);
+                assert_eq!(Feed::Rss(expected_feed), feed);
             });
     }
+
+    #[test]
+    fn skip_rules() {
+        use chrono::TimeZone;
+
+        let file = File::open(get_test_dir().join("skip.xml")).unwrap();
+        let mut feed = xml_from_reader("", BufReader::new(file)).unwrap();
+        let channel = &feed.channel;
+        assert_eq!(
+            channel.skip_hours.hours().collect::<Vec<_>>(),
+            (0..8).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            channel.skip_days.days().collect::<Vec<_>>(),
+            vec![Weekday::Sat, Weekday::Sun]
+        );
+        assert_eq!(channel.ttl, Some(60));
+
+        // Tuesday
+        let noon = Utc.with_ymd_and_hms(2024, 3, 5, 12, 0, 0).unwrap();
+        let early = Utc.with_ymd_and_hms(2024, 3, 5, 3, 30, 0).unwrap();
+        let saturday = Utc.with_ymd_and_hms(2024, 3, 9, 12, 0, 0).unwrap();
+        assert!(feed.should_update(early), "never polled");
+        feed.channel.last_updated = Some(early - chrono::Duration::days(1));
+        assert!(feed.should_update(noon));
+        assert!(!feed.should_update(early));
+        assert!(!feed.should_update(saturday));
+
+        let feed = Feed::Rss(feed);
+        assert_eq!(feed.ttl(), Some(std::time::Duration::from_secs(3600)));
+        assert_eq!(
+            feed.next_poll(early, &Default::default()),
+            Some(Utc.with_ymd_and_hms(2024, 3, 5, 4, 0, 0).unwrap())
+        );
+
+        // Stored and loaded again the same
+        let json = serde_json::to_string(&feed).unwrap();
+        assert_eq!(serde_json::from_str::<Feed>(&json).unwrap(), feed);
+        // As stored before skipHours held its <hour> elements
+        assert_eq!(
+            serde_json::from_str::<SkipHours>("[]").unwrap(),
+            SkipHours::default()
+        );
+
+        let file = File::open(get_test_dir().join("skip_lenient.xml")).unwrap();
+        let feed = xml_from_reader("", BufReader::new(file)).unwrap();
+        let channel = &feed.channel;
+        assert_eq!(channel.skip_hours.hours().collect::<Vec<_>>(), vec![12, 0]);
+        assert_eq!(
+            channel.skip_days.days().collect::<Vec<_>>(),
+            vec![Weekday::Wed]
+        );
+        assert_eq!(channel.ttl, Some(90));
+    }
 }
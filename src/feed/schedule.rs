@@ -0,0 +1,347 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    time::Duration,
+};
+
+// Largest part of its interval a poll is put off by, so feeds that were
+// due together drift apart
+const JITTER_DIVISOR: u32 = 10;
+
+// Posts looked at to learn how often a feed posts
+const HISTORY: usize = 20;
+
+// Scheduling settings from the config, for feeds that don't set their own
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Defaults {
+    pub interval: Duration,
+    pub adaptive: bool,
+    // Bounds on learned intervals
+    pub min_interval: Duration,
+    pub max_interval: Duration,
+}
+
+impl Default for Defaults {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(600),
+            adaptive: false,
+            min_interval: Duration::from_secs(5 * 60),
+            max_interval: Duration::from_secs(24 * 60 * 60),
+        }
+    }
+}
+
+// How often a feed is polled, set with ~edit
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct Schedule {
+    // Seconds between polls, replaces the configured interval and the ttl
+    pub interval: Option<u64>,
+    // Bounds in seconds on the interval, however it was chosen
+    pub min_interval: Option<u64>,
+    pub max_interval: Option<u64>,
+    // Poll as often as the feed posts, None follows the config
+    pub adaptive: Option<bool>,
+    // Seconds between polls learned from the feed's posts
+    pub learned_interval: Option<u64>,
+    // When polls found new items, oldest first, for feeds that don't date them
+    pub new_items: Vec<DateTime<Utc>>,
+}
+
+impl Schedule {
+    pub fn is_adaptive(&self, defaults: &Defaults) -> bool {
+        self.adaptive.unwrap_or(defaults.adaptive)
+    }
+
+    // Time between polls: the feed's own interval, or the learned one in
+    // adaptive mode, or the configured one. Without its own interval the feed
+    // isn't polled more often than its ttl asks. Kept within the bounds, the
+    // maximum winning over a larger minimum.
+    pub fn interval(&self, defaults: &Defaults, ttl: Option<Duration>) -> Duration {
+        let interval = match (self.interval, self.learned_interval) {
+            (Some(interval), _) => Duration::from_secs(interval),
+            (None, Some(learned)) if self.is_adaptive(defaults) => {
+                let learned = Duration::from_secs(learned)
+                    .max(defaults.min_interval)
+                    .min(defaults.max_interval);
+                ttl.map_or(learned, |ttl| ttl.max(learned))
+            }
+            (None, _) => ttl.map_or(defaults.interval, |ttl| ttl.max(defaults.interval)),
+        };
+        let interval = self
+            .min_interval
+            .map_or(interval, |min| interval.max(Duration::from_secs(min)));
+        self.max_interval
+            .map_or(interval, |max| interval.min(Duration::from_secs(max)))
+    }
+
+    // Where the interval comes from, for ~status
+    pub fn describe(&self, defaults: &Defaults, ttl: Option<Duration>) -> &'static str {
+        match (self.interval, self.learned_interval) {
+            (Some(_), _) => "set for the feed",
+            (None, Some(_)) if self.is_adaptive(defaults) => "learned from its posts",
+            (None, None) if self.is_adaptive(defaults) => "configured, too few posts to learn from",
+            (None, _) if ttl.is_some_and(|ttl| ttl > defaults.interval) => {
+                "the feed's ttl or update period"
+            }
+            (None, _) => "configured",
+        }
+    }
+
+    // When the feed last polled at last_updated is due again
+    pub fn next_poll(
+        &self,
+        last_updated: DateTime<Utc>,
+        defaults: &Defaults,
+        ttl: Option<Duration>,
+    ) -> DateTime<Utc> {
+        chrono::Duration::from_std(self.interval(defaults, ttl))
+            .ok()
+            .and_then(|interval| last_updated.checked_add_signed(interval))
+            .unwrap_or(DateTime::<Utc>::MAX_UTC)
+    }
+
+    // Learn how often the feed posts after a poll, from the dates of its
+    // items, or from when polls found new items when fewer than two are dated
+    pub fn learn(&mut self, item_dates: &[DateTime<Utc>], found_new: bool, now: DateTime<Utc>) {
+        if found_new {
+            self.new_items.push(now);
+            let excess = self.new_items.len().saturating_sub(HISTORY);
+            self.new_items.drain(..excess);
+        }
+
+        let mut posts: Vec<_> = item_dates
+            .iter()
+            .copied()
+            .filter(|date| *date <= now)
+            .collect();
+        posts.sort_unstable_by(|a, b| b.cmp(a));
+        posts.dedup();
+        posts.truncate(HISTORY);
+        if posts.len() < 2 {
+            posts = self.new_items.iter().rev().copied().collect();
+        }
+        self.learned_interval = learned_interval(&posts, now);
+    }
+}
+
+// Up to a tenth of the interval, the same for the same key so the time a feed
+// is due doesn't change between looking and polling
+pub fn jitter(interval: Duration, key: impl Hash) -> Duration {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    let fraction = (hasher.finish() % 1000) as u32;
+    interval / JITTER_DIVISOR * fraction / 1000
+}
+
+// Half the mean time between the posts, newest first, so a new post waits a
+// quarter of that on average. A feed that has been quiet for longer than it
+// usually is gets polled less often the longer it stays quiet.
+fn learned_interval(posts: &[DateTime<Utc>], now: DateTime<Utc>) -> Option<u64> {
+    let (newest, oldest) = (posts.first()?, posts.last()?);
+    if posts.len() < 2 {
+        return None;
+    }
+    let gap = (*newest - *oldest).num_seconds() / (posts.len() as i64 - 1);
+    let quiet = (now - *newest).num_seconds();
+    u64::try_from(gap.max(quiet) / 2)
+        .ok()
+        .map(|secs| secs.max(1))
+}
+
+// Parse an interval given to ~edit: seconds, or a number with an s, m, h or d
+// suffix. None and default clear it.
+pub fn parse_interval(s: &str) -> anyhow::Result<Option<u64>> {
+    let s = s.trim().to_lowercase();
+    if s == "none" || s == "default" {
+        return Ok(None);
+    }
+
+    let (number, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(idx) => s.split_at(idx),
+        None => (s.as_str(), "s"),
+    };
+    let multiplier = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => anyhow::bail!("unknown interval unit in {}", s),
+    };
+    let number: u64 = number
+        .parse()
+        .map_err(|e| anyhow::anyhow!("invalid interval {}: {}", s, e))?;
+    match number.checked_mul(multiplier) {
+        Some(0) | None => anyhow::bail!("interval {} is out of range", s),
+        Some(seconds) => Ok(Some(seconds)),
+    }
+}
+
+// Show an interval the way ~edit takes it, largest units first
+pub fn format_interval(interval: Duration) -> String {
+    let mut secs = interval.as_secs();
+    if secs == 0 {
+        return "0s".to_string();
+    }
+    let mut parts = Vec::new();
+    for (unit, size) in [("d", 24 * 60 * 60), ("h", 60 * 60), ("m", 60), ("s", 1)] {
+        if secs >= size {
+            parts.push(format!("{}{}", secs / size, unit));
+            secs %= size;
+        }
+    }
+    parts.join(" ")
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{TimeZone, Utc};
+    use std::time::Duration;
+
+    use super::{format_interval, jitter, parse_interval, Defaults, Schedule};
+
+    #[test]
+    fn bounded_intervals() {
+        let defaults = Defaults::default();
+        let hour = Duration::from_secs(3600);
+
+        let schedule = Schedule::default();
+        assert_eq!(schedule.interval(&defaults, None), defaults.interval);
+        assert_eq!(schedule.interval(&defaults, Some(hour)), hour);
+        assert_eq!(
+            schedule.interval(&defaults, Some(Duration::from_secs(60))),
+            defaults.interval
+        );
+
+        let schedule = Schedule {
+            interval: Some(120),
+            ..Default::default()
+        };
+        assert_eq!(
+            schedule.interval(&defaults, Some(hour)),
+            Duration::from_secs(120)
+        );
+
+        let schedule = Schedule {
+            interval: Some(60),
+            min_interval: Some(300),
+            max_interval: Some(1800),
+            ..Default::default()
+        };
+        assert_eq!(schedule.interval(&defaults, None), Duration::from_secs(300));
+        let schedule = Schedule {
+            interval: None,
+            ..schedule
+        };
+        assert_eq!(
+            schedule.interval(&defaults, Some(hour)),
+            Duration::from_secs(1800)
+        );
+
+        let conflicting = Schedule {
+            min_interval: Some(7200),
+            max_interval: Some(3600),
+            ..Default::default()
+        };
+        assert_eq!(conflicting.interval(&defaults, None), hour);
+
+        let last = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+        assert_eq!(
+            schedule.next_poll(last, &defaults, None),
+            Utc.with_ymd_and_hms(2024, 1, 1, 12, 10, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn staggered_polls() {
+        let interval = Duration::from_secs(600);
+        let a = jitter(interval, ("https://a.example.com/feed", 1_700_000_000));
+        assert_eq!(
+            a,
+            jitter(interval, ("https://a.example.com/feed", 1_700_000_000))
+        );
+        assert!(a < interval / 10);
+
+        let offsets: std::collections::HashSet<_> = (0..20)
+            .map(|feed| jitter(interval, format!("https://example.com/{}", feed)))
+            .collect();
+        assert!(offsets.len() > 10, "{:?}", offsets);
+    }
+
+    #[test]
+    fn adaptive_intervals() {
+        let now = Utc.with_ymd_and_hms(2024, 1, 8, 12, 0, 0).unwrap();
+        let adaptive = Defaults {
+            adaptive: true,
+            ..Default::default()
+        };
+        let weekly: Vec<_> = (0..5)
+            .map(|week| now - chrono::Duration::weeks(week))
+            .collect();
+        let busy: Vec<_> = (0..20)
+            .map(|post| now - chrono::Duration::minutes(2 * post))
+            .collect();
+
+        let mut schedule = Schedule::default();
+        schedule.learn(&weekly, false, now);
+        assert_eq!(schedule.learned_interval, Some(302_400));
+        assert_eq!(
+            schedule.interval(&Defaults::default(), None),
+            Duration::from_secs(600)
+        );
+        assert_eq!(schedule.interval(&adaptive, None), adaptive.max_interval);
+
+        schedule.learn(&busy, false, now);
+        assert_eq!(schedule.learned_interval, Some(60));
+        assert_eq!(schedule.interval(&adaptive, None), adaptive.min_interval);
+        let hour = Duration::from_secs(3600);
+        assert_eq!(schedule.interval(&adaptive, Some(hour)), hour);
+
+        // Quiet for a day after posting every two minutes
+        schedule.learn(&busy, false, now + chrono::Duration::days(1));
+        assert_eq!(schedule.learned_interval, Some(43_200));
+
+        // Undated items fall back to when polls found new ones
+        let mut schedule = Schedule {
+            adaptive: Some(true),
+            max_interval: Some(1800),
+            ..Default::default()
+        };
+        schedule.learn(&[], true, now);
+        assert_eq!(schedule.learned_interval, None);
+        assert_eq!(
+            schedule.interval(&Defaults::default(), None),
+            Duration::from_secs(600)
+        );
+        schedule.learn(&[now], true, now + chrono::Duration::hours(2));
+        assert_eq!(schedule.learned_interval, Some(3600));
+        assert_eq!(
+            schedule.interval(&Defaults::default(), None),
+            Duration::from_secs(1800)
+        );
+        for _ in 0..30 {
+            schedule.learn(&[], true, now);
+        }
+        assert_eq!(schedule.new_items.len(), 20);
+    }
+
+    #[test]
+    fn interval_arguments() {
+        assert_eq!(parse_interval("90").unwrap(), Some(90));
+        assert_eq!(parse_interval("15m").unwrap(), Some(900));
+        assert_eq!(parse_interval("2H").unwrap(), Some(7200));
+        assert_eq!(parse_interval("30d").unwrap(), Some(2_592_000));
+        assert_eq!(parse_interval("none").unwrap(), None);
+        assert!(parse_interval("0").is_err());
+        assert!(parse_interval("5w").is_err());
+        assert!(parse_interval("m").is_err());
+        assert!(parse_interval("99999999999999999999d").is_err());
+
+        assert_eq!(format_interval(Duration::from_secs(0)), "0s");
+        assert_eq!(format_interval(Duration::from_secs(90)), "1m 30s");
+        assert_eq!(format_interval(Duration::from_secs(302_400)), "3d 12h");
+    }
+}
@@ -0,0 +1,152 @@
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+
+// How often a feed says it is updated with the RSS syndication module
+// (https://web.resource.org/rss/1.0/modules/syndication/): updateFrequency
+// times every updatePeriod, counted from updateBase
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Syndication {
+    pub interval: Duration,
+    pub base: Option<DateTime<Utc>>,
+}
+
+impl Syndication {
+    // The period defaults to daily and the frequency to 1 when only one of
+    // them is given, None when neither is or the period is unknown
+    pub fn parse(
+        period: Option<&str>,
+        frequency: Option<&str>,
+        base: Option<&str>,
+    ) -> Option<Self> {
+        if period.is_none() && frequency.is_none() {
+            return None;
+        }
+
+        let period = match period.map(|p| p.trim().to_lowercase()).as_deref() {
+            None | Some("daily") => 24 * 60 * 60,
+            Some("hourly") => 60 * 60,
+            Some("weekly") => 7 * 24 * 60 * 60,
+            Some("monthly") => 30 * 24 * 60 * 60,
+            Some("yearly") => 365 * 24 * 60 * 60,
+            Some(_) => return None,
+        };
+        // Anything but a positive number is taken as the default
+        let frequency = frequency
+            .and_then(|f| f.trim().parse::<u64>().ok())
+            .filter(|f| *f > 0)
+            .unwrap_or(1);
+
+        Some(Self {
+            interval: Duration::from_secs((period / frequency).max(1)),
+            base: base.and_then(|base| super::date::parse(base.trim()).ok()),
+        })
+    }
+
+    // The first update at or after time, time itself without a base
+    pub fn next_update(&self, time: DateTime<Utc>) -> DateTime<Utc> {
+        let Some(base) = self.base else {
+            return time;
+        };
+        let step = self.interval.as_secs() as i64;
+        let since = (time - base).num_seconds();
+        // Rounded up, the base may also come after time
+        let steps = -(-since).div_euclid(step);
+        steps
+            .checked_mul(step)
+            .and_then(chrono::Duration::try_seconds)
+            .and_then(|offset| base.checked_add_signed(offset))
+            .unwrap_or(time)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{TimeZone, Utc};
+    use std::{fs::File, io::BufReader, path::PathBuf, time::Duration};
+
+    use super::Syndication;
+    use crate::feed::{atom, rss};
+
+    fn get_test_dir() -> PathBuf {
+        std::env::current_dir()
+            .expect("failed to get current directory")
+            .join("test")
+    }
+
+    #[test]
+    fn update_periods() {
+        let hour = Duration::from_secs(3600);
+        let parse = |period, frequency| Syndication::parse(period, frequency, None);
+        assert_eq!(parse(None, None), None);
+        assert_eq!(parse(Some("hourly"), Some("1")).unwrap().interval, hour);
+        assert_eq!(parse(Some(" Daily "), Some("24")).unwrap().interval, hour);
+        assert_eq!(parse(None, Some("24")).unwrap().interval, hour);
+        assert_eq!(parse(Some("hourly"), None).unwrap().interval, hour);
+        assert_eq!(
+            parse(Some("weekly"), Some("0")).unwrap().interval,
+            hour * 168
+        );
+        assert_eq!(
+            parse(Some("daily"), Some("often")).unwrap().interval,
+            hour * 24
+        );
+        assert_eq!(
+            parse(Some("hourly"), Some("7200")).unwrap().interval,
+            Duration::from_secs(1)
+        );
+        assert_eq!(parse(Some("fortnightly"), Some("1")), None);
+
+        let twice_daily =
+            Syndication::parse(Some("daily"), Some("2"), Some("2000-01-01T12:00+00:00")).unwrap();
+        assert_eq!(
+            twice_daily.base,
+            Some(Utc.with_ymd_and_hms(2000, 1, 1, 12, 0, 0).unwrap())
+        );
+        let time = Utc.with_ymd_and_hms(2024, 3, 5, 13, 30, 0).unwrap();
+        assert_eq!(
+            twice_daily.next_update(time),
+            Utc.with_ymd_and_hms(2024, 3, 6, 0, 0, 0).unwrap()
+        );
+        let update = Utc.with_ymd_and_hms(2024, 3, 5, 12, 0, 0).unwrap();
+        assert_eq!(twice_daily.next_update(update), update);
+        let before = Utc.with_ymd_and_hms(1999, 12, 31, 23, 0, 0).unwrap();
+        assert_eq!(
+            twice_daily.next_update(before),
+            Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap()
+        );
+
+        let bad_base = Syndication::parse(Some("hourly"), None, Some("someday")).unwrap();
+        assert_eq!(bad_base.base, None);
+        assert_eq!(bad_base.next_update(time), time);
+    }
+
+    #[test]
+    fn feed_elements() {
+        let file = File::open(get_test_dir().join("syndication.xml")).unwrap();
+        let feed = rss::xml_from_reader("", BufReader::new(file)).unwrap();
+        let channel = &feed.channel;
+        assert_eq!(
+            channel.sy_update_period.as_deref().map(str::trim),
+            Some("hourly")
+        );
+        assert_eq!(
+            channel.sy_update_frequency.as_deref().map(str::trim),
+            Some("2")
+        );
+        assert_eq!(
+            channel.sy_update_base.as_deref(),
+            Some("2000-01-01T12:00+00:00")
+        );
+
+        let file = File::open(get_test_dir().join("rdf.xml")).unwrap();
+        let feed = rss::xml_from_reader("", BufReader::new(file)).unwrap();
+        assert_eq!(feed.channel.sy_update_period.as_deref(), Some("daily"));
+        assert_eq!(feed.channel.sy_update_frequency.as_deref(), Some("4"));
+
+        let file = File::open(get_test_dir().join("syndication_atom.xml")).unwrap();
+        let feed = atom::xml_from_reader("", BufReader::new(file)).unwrap();
+        assert_eq!(feed.sy_update_period.as_deref(), Some("weekly"));
+        assert_eq!(feed.sy_update_frequency, None);
+        assert_eq!(feed.sy_update_base, None);
+    }
+}
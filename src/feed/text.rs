@@ -0,0 +1,207 @@
+use quick_xml::{
+    events::{BytesEnd, BytesStart, BytesText, Event},
+    Reader, Writer,
+};
+use serde::{de, Deserialize, Deserializer, Serialize};
+use std::fmt;
+
+use super::html::{self, Markdown};
+
+// Atom text construct (RFC 4287 section 3.1)
+#[derive(Serialize, Clone, Debug, Hash, PartialEq, Eq, Default)]
+pub struct Text {
+    #[serde(rename = "@type")]
+    pub kind: TextType,
+    #[serde(rename = "$value")]
+    pub value: String,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, Hash, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TextType {
+    #[default]
+    Text,
+    Html,
+    Xhtml,
+}
+
+impl From<&str> for TextType {
+    fn from(kind: &str) -> Self {
+        match kind.trim().to_lowercase().as_str() {
+            "html" | "text/html" => Self::Html,
+            "xhtml" | "application/xhtml+xml" => Self::Xhtml,
+            _ => Self::Text,
+        }
+    }
+}
+
+impl From<&str> for Text {
+    fn from(value: &str) -> Self {
+        Self {
+            kind: TextType::Text,
+            value: value.to_string(),
+        }
+    }
+}
+
+// Accepts both the XML element (with a type attribute) and a bare string,
+// which is how text was stored in the database before text constructs
+impl<'de> Deserialize<'de> for Text {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct TextVisitor;
+
+        impl<'de> de::Visitor<'de> for TextVisitor {
+            type Value = Text;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "an atom text construct")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Text, E> {
+                Ok(value.into())
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Text, A::Error> {
+                let mut text = Text::default();
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "@type" => text.kind = map.next_value::<String>()?.as_str().into(),
+                        "$value" | "$text" => text.value = map.next_value()?,
+                        _ => {
+                            map.next_value::<de::IgnoredAny>()?;
+                        }
+                    }
+                }
+                Ok(text)
+            }
+        }
+
+        deserializer.deserialize_any(TextVisitor)
+    }
+}
+
+impl Text {
+    // Text with any markup removed, for places that can't render it
+    pub fn to_plain(&self) -> String {
+        match self.kind {
+            TextType::Text => self.value.trim().to_string(),
+            TextType::Html | TextType::Xhtml => html::to_plain(&self.value),
+        }
+    }
+
+    // Text formatted for an embed description, links resolved against base
+    pub fn to_markdown(&self, base: Option<&str>) -> Markdown {
+        match self.kind {
+            TextType::Text => Markdown {
+                text: self.value.trim().to_string(),
+                image: None,
+            },
+            TextType::Html | TextType::Xhtml => html::to_markdown(&self.value, base),
+        }
+    }
+}
+
+// Deserialize a text construct straight into plain text
+pub fn plain<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Text::deserialize(deserializer).map(|t| t.to_plain())
+}
+
+pub fn plain_opt<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<Text>::deserialize(deserializer).map(|t| t.map(|t| t.to_plain()))
+}
+
+pub fn is_xml_mime_type(mime: impl AsRef<str>) -> bool {
+    let mime = mime.as_ref().trim().to_lowercase();
+    mime.ends_with("+xml") || mime.ends_with("/xml")
+}
+
+fn is_markup_construct(start: &BytesStart) -> bool {
+    let constructs: &[&[u8]] = &[b"title", b"subtitle", b"summary", b"content", b"rights"];
+    if !constructs.contains(&start.local_name().as_ref()) {
+        return false;
+    }
+
+    match start.try_get_attribute("type") {
+        Ok(Some(attr)) => attr.unescape_value().is_ok_and(|kind| {
+            TextType::from(kind.as_ref()) == TextType::Xhtml || is_xml_mime_type(&kind)
+        }),
+        _ => false,
+    }
+}
+
+// Serialize the children of the element just opened back into markup,
+// leaving out the wrapping xhtml div required by RFC 4287 section 3.1.1.3
+fn read_markup(reader: &mut Reader<&[u8]>) -> anyhow::Result<String> {
+    let mut inner = Writer::new(Vec::new());
+    let mut buf = Vec::new();
+    let mut depth = 0usize;
+    let mut seen_element = false;
+    let mut wrapped = false;
+
+    loop {
+        let event = reader.read_event_into(&mut buf)?;
+        match event {
+            Event::Start(ref e) => {
+                depth += 1;
+                if depth == 1 && !seen_element && e.local_name().as_ref() == b"div" {
+                    seen_element = true;
+                    wrapped = true;
+                    buf.clear();
+                    continue;
+                }
+                seen_element = true;
+            }
+            Event::Empty(_) => seen_element = true,
+            Event::End(_) => {
+                if depth == 0 {
+                    break;
+                }
+                depth -= 1;
+                if depth == 0 && wrapped {
+                    buf.clear();
+                    continue;
+                }
+            }
+            Event::Eof => anyhow::bail!("document ended inside of an xhtml text construct"),
+            _ => (),
+        }
+        inner.write_event(event)?;
+        buf.clear();
+    }
+
+    Ok(String::from_utf8_lossy(&inner.into_inner()).to_string())
+}
+
+// Replace the inline markup of xhtml (and other XML typed) text constructs with
+// escaped text so they can be deserialized as strings like html constructs
+pub fn normalize_xhtml(bytes: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut reader = Reader::from_reader(bytes);
+    let mut writer = Writer::new(Vec::with_capacity(bytes.len()));
+    let mut buf = Vec::new();
+
+    loop {
+        match reader.read_event_into(&mut buf)? {
+            Event::Eof => break,
+            Event::Start(e) if is_markup_construct(&e) => {
+                let name = String::from_utf8_lossy(e.name().as_ref()).to_string();
+                writer.write_event(Event::Start(e.into_owned()))?;
+                let markup = read_markup(&mut reader)?;
+                writer.write_event(Event::Text(BytesText::new(&markup)))?;
+                writer.write_event(Event::End(BytesEnd::new(name)))?;
+            }
+            e => writer.write_event(e)?,
+        }
+        buf.clear();
+    }
+
+    Ok(writer.into_inner())
+}
@@ -0,0 +1,694 @@
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use hyper::{
+    body::HttpBody,
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use rand::{distributions::Alphanumeric, Rng};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Sha256, Sha384, Sha512};
+use std::{convert::Infallible, future::Future, net::SocketAddr, sync::Arc, time::Duration};
+use tokio::sync::{mpsc, RwLock};
+use tracing::{debug, info, instrument, warn};
+
+use super::fetch::{self, Secret};
+use super::Feed;
+
+// Lease asked for when the config doesn't set one
+const DEFAULT_LEASE: u64 = 10 * 24 * 60 * 60;
+// Longest lease taken from a hub, longer ones are renewed before they end
+const MAX_LEASE: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+// Renew this long before the lease ends, or halfway through shorter leases
+const RENEW_BEFORE: Duration = Duration::from_secs(24 * 60 * 60);
+// Longest wait for a hub to verify a subscription request before asking again
+const VERIFY_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+// First wait before asking a hub that failed again, doubled for every failure
+const RETRY_BASE: Duration = Duration::from_secs(15 * 60);
+// Longest wait before asking a hub that failed again
+const RETRY_MAX: Duration = Duration::from_secs(24 * 60 * 60);
+// A feed that gets pushes is still polled this often, in case the hub fails
+// without saying so
+pub const PUSHED_POLL_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+// Callback server settings, from the config
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub struct WebSubConfig {
+    // Address the callback server listens on
+    pub listen: SocketAddr,
+    // Public url of the callback server, which hubs must be able to reach
+    pub callback_url: String,
+    #[serde(default = "default_lease")]
+    pub lease_seconds: u64,
+}
+
+fn default_lease() -> u64 {
+    DEFAULT_LEASE
+}
+
+// A feed's subscription at the hub it advertises
+#[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct Subscription {
+    pub hub: String,
+    pub topic: String,
+    // Last segment of the callback url, tells which feed a request is for
+    pub id: String,
+    // Key of the signatures on pushed content
+    pub secret: Secret,
+    // When the last subscription request was sent, until the hub answers
+    pub requested: Option<DateTime<Utc>>,
+    // End of the lease the hub verified, updates are pushed until then
+    pub expires: Option<DateTime<Utc>>,
+    // Subscription requests that failed in a row
+    pub failures: u32,
+    pub retry_at: Option<DateTime<Utc>>,
+}
+
+impl Subscription {
+    pub fn new(hub: impl Into<String>, topic: impl Into<String>) -> Self {
+        Self {
+            hub: hub.into(),
+            topic: topic.into(),
+            id: random_token(32),
+            secret: Secret(random_token(40)),
+            ..Default::default()
+        }
+    }
+
+    // Whether the hub pushes updates, so polling can wait
+    pub fn active(&self, now: DateTime<Utc>) -> bool {
+        self.expires.is_some_and(|expires| expires > now)
+    }
+
+    // Whether to ask the hub for a new lease
+    fn due(&self, now: DateTime<Utc>, lease: Duration) -> bool {
+        if self.retry_at.is_some_and(|retry_at| retry_at > now) {
+            return false;
+        }
+        if self
+            .requested
+            .is_some_and(|requested| now - requested < to_chrono(VERIFY_TIMEOUT))
+        {
+            return false;
+        }
+        match self.expires {
+            None => true,
+            Some(expires) => expires - now < to_chrono(RENEW_BEFORE.min(lease / 2)),
+        }
+    }
+
+    fn record_failure(&mut self, now: DateTime<Utc>) {
+        self.requested = None;
+        self.failures = self.failures.saturating_add(1);
+        let wait = super::health::backoff(RETRY_BASE, self.failures - 1).min(RETRY_MAX);
+        self.retry_at = Some(now + to_chrono(wait));
+    }
+}
+
+fn to_chrono(duration: Duration) -> chrono::Duration {
+    chrono::Duration::from_std(duration).unwrap_or(chrono::Duration::MAX)
+}
+
+fn random_token(len: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
+// Keep the subscription of feed in step with the hub its latest update
+// advertises, starting over when the hub or topic changed. Hubs without https
+// are left alone, the secret would be sent in the clear and pushes couldn't be
+// trusted without it, so those feeds are only polled.
+pub fn track_hub(feed: &mut Feed, update: &Feed) {
+    let hub = update.hub().filter(|(hub, _)| {
+        let https = Url::parse(hub).is_ok_and(|hub| hub.scheme() == "https");
+        if !https {
+            debug!(
+                "Not subscribing to {} at hub {} without https.",
+                feed.url(),
+                hub
+            );
+        }
+        https
+    });
+    match (hub, feed.websub()) {
+        (None, None) => (),
+        (None, Some(_)) => {
+            info!("Feed {} no longer advertises a hub.", feed.url());
+            *feed.websub_mut() = None;
+        }
+        (Some((hub, topic)), Some(sub)) if sub.hub == hub && sub.topic == topic => (),
+        (Some((hub, topic)), _) => {
+            info!("Feed {} advertises hub {} for {}.", feed.url(), hub, topic);
+            *feed.websub_mut() = Some(Subscription::new(hub, topic));
+        }
+    }
+}
+
+// The form asking the hub to push the topic to the callback server
+fn subscribe_form(config: &WebSubConfig, sub: &Subscription) -> Vec<(&'static str, String)> {
+    vec![
+        ("hub.mode", "subscribe".to_owned()),
+        (
+            "hub.callback",
+            format!("{}/{}", config.callback_url.trim_end_matches('/'), sub.id),
+        ),
+        ("hub.topic", sub.topic.clone()),
+        ("hub.lease_seconds", config.lease_seconds.to_string()),
+        ("hub.secret", sub.secret.0.clone()),
+    ]
+}
+
+// Ask the hubs of feeds without a subscription, or with one about to end, for
+// a lease. The hubs verify the requests through the callback server later.
+#[instrument(skip(feeds))]
+pub async fn renew(feeds: &RwLock<Vec<Feed>>, config: &WebSubConfig) {
+    let now = Utc::now();
+    let lease = Duration::from_secs(config.lease_seconds);
+    let due = {
+        let mut guard = feeds.write().await;
+        guard
+            .iter_mut()
+            .filter_map(|feed| {
+                let url = feed.url();
+                let request = feed.request_options().clone();
+                let sub = feed.websub_mut().as_mut()?;
+                if !sub.due(now, lease) {
+                    return None;
+                }
+                if sub.requested.is_some() {
+                    warn!(
+                        "Hub {} never verified the subscription to {}.",
+                        sub.hub, sub.topic
+                    );
+                    sub.record_failure(now);
+                    return None;
+                }
+                // Marked before asking, the hub may verify before it answers
+                sub.requested = Some(now);
+                // The hub is asked through the feed's proxy, its credentials
+                // and certificate options only go along when it shares the
+                // feed's origin
+                let options = fetch::Options {
+                    request,
+                    ..Default::default()
+                };
+                let options = match (Url::parse(&url), Url::parse(&sub.hub)) {
+                    (Ok(url), Ok(hub)) => options.elsewhere(&url, &hub),
+                    _ => options,
+                };
+                Some((sub.clone(), options))
+            })
+            .collect::<Vec<_>>()
+    };
+
+    for (sub, options) in due {
+        info!("Subscribing to {} at hub {}.", sub.topic, sub.hub);
+        let form = subscribe_form(config, &sub);
+        let form = form
+            .iter()
+            .map(|(k, v)| (*k, v.as_str()))
+            .collect::<Vec<_>>();
+        let sent = match Url::parse(&sub.hub) {
+            Err(e) => Err(e.into()),
+            Ok(hub) => fetch::post_form(&hub, &form, &options).await,
+        };
+        if let Err(e) = sent {
+            warn!(
+                "Hub {} refused the subscription to {}: {}",
+                sub.hub, sub.topic, e
+            );
+            let mut guard = feeds.write().await;
+            if let Some(s) = guard
+                .iter_mut()
+                .filter_map(|feed| feed.websub_mut().as_mut())
+                .find(|s| s.id == sub.id)
+            {
+                s.record_failure(Utc::now());
+            }
+        }
+    }
+}
+
+// Content a hub pushed for a feed, with a valid signature
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Push {
+    pub url: String,
+    pub feed: Feed,
+}
+
+// Bind the callback server, which verifies subscriptions of the feeds and
+// sends the content hubs push for them on pushes. Returns the bound address
+// and the server to run.
+pub fn serve(
+    listen: SocketAddr,
+    feeds: Arc<RwLock<Vec<Feed>>>,
+    pushes: mpsc::Sender<Push>,
+) -> anyhow::Result<(SocketAddr, impl Future<Output = anyhow::Result<()>>)> {
+    let make_service = make_service_fn(move |_| {
+        let feeds = feeds.clone();
+        let pushes = pushes.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let feeds = feeds.clone();
+                let pushes = pushes.clone();
+                async move { Ok::<_, Infallible>(handle(req, &feeds, &pushes).await) }
+            }))
+        }
+    });
+
+    let server = Server::try_bind(&listen)?.serve(make_service);
+    let addr = server.local_addr();
+    info!("WebSub callback server listening on {}.", addr);
+    Ok((addr, async move { Ok(server.await?) }))
+}
+
+fn respond(status: StatusCode, body: impl Into<Body>) -> Response<Body> {
+    let mut response = Response::new(body.into());
+    *response.status_mut() = status;
+    response
+}
+
+#[instrument(skip_all, fields(method = %req.method(), path = %req.uri().path()))]
+async fn handle(
+    req: Request<Body>,
+    feeds: &RwLock<Vec<Feed>>,
+    pushes: &mpsc::Sender<Push>,
+) -> Response<Body> {
+    let id = req
+        .uri()
+        .path()
+        .rsplit('/')
+        .find(|segment| !segment.is_empty())
+        .unwrap_or_default()
+        .to_owned();
+
+    match *req.method() {
+        Method::GET => verify(&req, &id, feeds).await,
+        Method::POST => receive(req, &id, feeds, pushes).await,
+        _ => respond(StatusCode::METHOD_NOT_ALLOWED, Body::empty()),
+    }
+}
+
+// Answer the hub checking that the subscription was asked for
+async fn verify(req: &Request<Body>, id: &str, feeds: &RwLock<Vec<Feed>>) -> Response<Body> {
+    let query = Url::parse(&format!("http://callback{}", req.uri()))
+        .map(|url| url.query_pairs().into_owned().collect::<Vec<_>>())
+        .unwrap_or_default();
+    let param = |name: &str| {
+        query
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    };
+
+    let now = Utc::now();
+    let mut guard = feeds.write().await;
+    let Some(sub) = guard
+        .iter_mut()
+        .filter_map(|feed| feed.websub_mut().as_mut())
+        .find(|sub| sub.id == id)
+    else {
+        return respond(StatusCode::NOT_FOUND, Body::empty());
+    };
+    if param("hub.topic") != Some(sub.topic.as_str()) {
+        warn!(
+            "Hub {} verified an unknown topic {:?}.",
+            sub.hub,
+            param("hub.topic")
+        );
+        return respond(StatusCode::NOT_FOUND, Body::empty());
+    }
+
+    match param("hub.mode") {
+        Some("subscribe") if sub.requested.is_some() || sub.active(now) => {
+            let lease = param("hub.lease_seconds")
+                .and_then(|lease| lease.parse().ok())
+                .map_or(Duration::from_secs(DEFAULT_LEASE), Duration::from_secs)
+                .min(MAX_LEASE);
+            info!(
+                "Hub {} pushes {} for {} seconds.",
+                sub.hub,
+                sub.topic,
+                lease.as_secs()
+            );
+            sub.expires = now.checked_add_signed(to_chrono(lease));
+            sub.requested = None;
+            sub.failures = 0;
+            sub.retry_at = None;
+            respond(
+                StatusCode::OK,
+                param("hub.challenge").unwrap_or_default().to_owned(),
+            )
+        }
+        Some("denied") => {
+            warn!(
+                "Hub {} denied the subscription to {}: {}",
+                sub.hub,
+                sub.topic,
+                param("hub.reason").unwrap_or("no reason given")
+            );
+            sub.expires = None;
+            sub.record_failure(now);
+            respond(StatusCode::OK, Body::empty())
+        }
+        // Never asked to unsubscribe, or to subscribe right now
+        _ => respond(StatusCode::NOT_FOUND, Body::empty()),
+    }
+}
+
+// Take content pushed by the hub, acknowledged even when it's ignored so the
+// hub doesn't retry it
+async fn receive(
+    req: Request<Body>,
+    id: &str,
+    feeds: &RwLock<Vec<Feed>>,
+    pushes: &mpsc::Sender<Push>,
+) -> Response<Body> {
+    let found = feeds.read().await.iter().find_map(|feed| {
+        let sub = feed.websub()?;
+        (sub.id == id).then(|| (feed.url(), sub.clone()))
+    });
+    let Some((url, sub)) = found else {
+        return respond(StatusCode::GONE, Body::empty());
+    };
+
+    let header = |name: &str| {
+        req.headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(String::from)
+    };
+    let signature = header("x-hub-signature");
+    let content_type = header(CONTENT_TYPE.as_str());
+    let body = match read_body(req.into_body()).await {
+        Ok(body) => body,
+        Err(e) => {
+            warn!("Failed reading content pushed for {}: {}", url, e);
+            return respond(StatusCode::PAYLOAD_TOO_LARGE, Body::empty());
+        }
+    };
+
+    if !verify_signature(sub.secret.0.as_bytes(), signature.as_deref(), &body) {
+        warn!(
+            "Ignoring content pushed for {} without a valid signature.",
+            url
+        );
+        return respond(StatusCode::ACCEPTED, Body::empty());
+    }
+
+    let parsed = Url::parse(&url).map_err(anyhow::Error::from).and_then(|u| {
+        super::from_document(
+            &u,
+            &body,
+            content_type.as_deref().map(fetch::media_type).as_deref(),
+            content_type
+                .as_deref()
+                .and_then(super::charset::from_content_type)
+                .as_deref(),
+        )
+    });
+    match parsed {
+        Err(e) => warn!("Ignoring content pushed for {}: {}", url, e),
+        Ok(feed) => {
+            debug!("Hub {} pushed {}.", sub.hub, url);
+            if let Err(e) = pushes.send(Push { url, feed }).await {
+                warn!("Failed to pass on pushed content: {}", e);
+            }
+        }
+    }
+    respond(StatusCode::ACCEPTED, Body::empty())
+}
+
+async fn read_body(mut body: Body) -> anyhow::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.data().await {
+        bytes.extend_from_slice(&chunk?);
+        if bytes.len() > fetch::MAX_BODY_SIZE {
+            anyhow::bail!("content is larger than {} bytes", fetch::MAX_BODY_SIZE);
+        }
+    }
+    Ok(bytes)
+}
+
+// Check X-Hub-Signature, the HMAC of the body keyed with the subscription
+// secret as method=hex
+pub fn verify_signature(secret: &[u8], header: Option<&str>, body: &[u8]) -> bool {
+    fn check<M: Mac + hmac::digest::KeyInit>(secret: &[u8], body: &[u8], expected: &[u8]) -> bool {
+        let Ok(mut mac) = <M as Mac>::new_from_slice(secret) else {
+            return false;
+        };
+        mac.update(body);
+        mac.verify_slice(expected).is_ok()
+    }
+
+    let Some((method, signature)) = header.and_then(|h| h.trim().split_once('=')) else {
+        return false;
+    };
+    let Ok(expected) = hex::decode(signature) else {
+        return false;
+    };
+    match method.to_lowercase().as_str() {
+        "sha1" => check::<Hmac<Sha1>>(secret, body, &expected),
+        "sha256" => check::<Hmac<Sha256>>(secret, body, &expected),
+        "sha384" => check::<Hmac<Sha384>>(secret, body, &expected),
+        "sha512" => check::<Hmac<Sha512>>(secret, body, &expected),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Utc;
+    use hmac::{Hmac, Mac};
+    use reqwest::{Client, StatusCode};
+    use sha2::Sha256;
+    use std::{
+        net::SocketAddr,
+        path::PathBuf,
+        sync::{Arc, Mutex},
+    };
+    use tokio::{
+        runtime,
+        sync::{mpsc, RwLock},
+    };
+
+    use super::{
+        renew, serve, subscribe_form, track_hub, verify_signature, Subscription, WebSubConfig,
+        MAX_LEASE,
+    };
+    use crate::feed::{atom, fetch, Feed};
+
+    fn get_test_dir() -> PathBuf {
+        std::env::current_dir()
+            .expect("failed to get current directory")
+            .join("test")
+    }
+
+    #[test]
+    fn signatures() {
+        let body = b"The quick brown fox jumps over the lazy dog";
+        let sha1 = "sha1=de7c9b85b8b78aa6bc8a7a36f70a90701c9db4d9";
+        let sha256 = "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8";
+        assert!(verify_signature(b"key", Some(sha1), body));
+        assert!(verify_signature(b"key", Some(sha256), body));
+        assert!(!verify_signature(b"other key", Some(sha256), body));
+        assert!(!verify_signature(b"key", Some(sha256), b"tampered"));
+        assert!(!verify_signature(b"key", Some("md5=abcd"), body));
+        assert!(!verify_signature(b"key", Some("sha256=not hex"), body));
+        assert!(!verify_signature(b"key", None, body));
+    }
+
+    // The subscription request goes through the feed's proxy, which refuses
+    // the tunnel to the hub
+    #[test]
+    fn renew_through_proxy() {
+        runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let seen = Arc::new(Mutex::new(Vec::new()));
+                let requests = seen.clone();
+                let proxy = fetch::test::serve(move |req| {
+                    requests.lock().unwrap().push(req.uri().to_string());
+                    let mut resp = hyper::Response::new(hyper::Body::empty());
+                    *resp.status_mut() = StatusCode::BAD_GATEWAY;
+                    resp
+                });
+
+                let mut feed = Feed::default();
+                feed.set_url("https://example.org/feed.xml");
+                feed.request_options_mut().proxy = Some(fetch::Proxy {
+                    url: proxy.to_string(),
+                    ..Default::default()
+                });
+                *feed.websub_mut() = Some(Subscription::new(
+                    "https://hub.example.org/",
+                    "https://example.org/feed.xml",
+                ));
+                let feeds = RwLock::new(vec![feed]);
+                let config = WebSubConfig {
+                    listen: "127.0.0.1:0".parse().unwrap(),
+                    callback_url: "https://rsspal.example.com/websub".to_owned(),
+                    lease_seconds: 3600,
+                };
+                renew(&feeds, &config).await;
+
+                assert_eq!(*seen.lock().unwrap(), vec!["hub.example.org:443"]);
+                let sub = feeds.read().await[0].websub().cloned().unwrap();
+                assert!(sub.requested.is_none());
+                assert_eq!(sub.failures, 1);
+            });
+    }
+
+    #[test]
+    fn insecure_hub() {
+        let doc = std::fs::read_to_string(get_test_dir().join("websub.xml")).unwrap();
+        let doc = doc.replace("https://hub.example.org/", "http://hub.example.org/");
+        let mut update = Feed::Atom(atom::xml_from_reader("websub", doc.as_bytes()).unwrap());
+        update.set_url("https://example.org/feed.atom");
+        let mut feed = update.clone();
+        track_hub(&mut feed, &update);
+        assert_eq!(feed.websub(), None);
+
+        // One from before the hub dropped https is let go
+        *feed.websub_mut() = Some(Subscription::new(
+            "https://hub.example.org/",
+            "https://example.org/feed.atom",
+        ));
+        track_hub(&mut feed, &update);
+        assert_eq!(feed.websub(), None);
+    }
+
+    // Plays the hub: verifies the subscription and pushes content to the
+    // callback server, like a hub would after a subscription request
+    #[test]
+    fn stand_in_hub() {
+        runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let doc = std::fs::read(get_test_dir().join("websub.xml")).unwrap();
+                let mut feed = Feed::Atom(atom::xml_from_reader("websub", doc.as_slice()).unwrap());
+                feed.set_url("https://example.org/feed.atom");
+                let update = feed.clone();
+                track_hub(&mut feed, &update);
+                let sub = feed.websub().cloned().unwrap();
+                assert_eq!(sub.hub, "https://hub.example.org/");
+                assert_eq!(sub.topic, "https://example.org/feed.atom");
+
+                let config = WebSubConfig {
+                    listen: "127.0.0.1:0".parse().unwrap(),
+                    callback_url: "https://rsspal.example.com/websub/".to_owned(),
+                    lease_seconds: 3600,
+                };
+                let form = subscribe_form(&config, &sub);
+                let callback = format!("https://rsspal.example.com/websub/{}", sub.id);
+                assert!(form.contains(&("hub.callback", callback)));
+                assert!(form.contains(&("hub.secret", sub.secret.0.clone())));
+
+                // Sent by renew before asking the hub
+                feed.websub_mut().as_mut().unwrap().requested = Some(Utc::now());
+                let feeds = Arc::new(RwLock::new(vec![feed]));
+                let (pushes, mut pushed) = mpsc::channel(8);
+                let (addr, server) = serve(config.listen, feeds.clone(), pushes).unwrap();
+                tokio::spawn(server);
+
+                let hub = Client::builder().no_proxy().build().unwrap();
+                let callback =
+                    |addr: SocketAddr, id: &str| format!("http://{}/websub/{}", addr, id);
+                let verify = |topic: &str| {
+                    hub.get(callback(addr, &sub.id)).query(&[
+                        ("hub.mode", "subscribe"),
+                        ("hub.topic", topic),
+                        ("hub.challenge", "c4allenge"),
+                        ("hub.lease_seconds", "3600"),
+                    ])
+                };
+
+                let resp = verify("https://example.org/other.atom")
+                    .send()
+                    .await
+                    .unwrap();
+                assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+                assert!(!feeds.read().await[0].websub().unwrap().active(Utc::now()));
+
+                let resp = verify(&sub.topic).send().await.unwrap();
+                assert_eq!(resp.status(), StatusCode::OK);
+                assert_eq!(resp.text().await.unwrap(), "c4allenge");
+                let verified = feeds.read().await[0].websub().cloned().unwrap();
+                assert!(verified.active(Utc::now()));
+                assert!(verified.requested.is_none());
+                assert!(!verified.active(Utc::now() + chrono::Duration::hours(2)));
+
+                // Leases past MAX_LEASE are cut short
+                let resp = hub
+                    .get(callback(addr, &sub.id))
+                    .query(&[
+                        ("hub.mode", "subscribe"),
+                        ("hub.topic", sub.topic.as_str()),
+                        ("hub.challenge", "again"),
+                        ("hub.lease_seconds", &u64::MAX.to_string()),
+                    ])
+                    .send()
+                    .await
+                    .unwrap();
+                assert_eq!(resp.status(), StatusCode::OK);
+                let expires = feeds.read().await[0].websub().unwrap().expires.unwrap();
+                let max = Utc::now() + chrono::Duration::from_std(MAX_LEASE).unwrap();
+                assert!(expires <= max && expires > max - chrono::Duration::hours(1));
+
+                let mut mac = Hmac::<Sha256>::new_from_slice(sub.secret.0.as_bytes()).unwrap();
+                mac.update(&doc);
+                let signature = format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
+                let push = |signature: &str| {
+                    hub.post(callback(addr, &sub.id))
+                        .header("Content-Type", "application/atom+xml")
+                        .header("X-Hub-Signature", signature)
+                        .body(doc.clone())
+                };
+
+                let resp = push(&signature).send().await.unwrap();
+                assert_eq!(resp.status(), StatusCode::ACCEPTED);
+                let received = pushed.try_recv().unwrap();
+                assert_eq!(received.url, "https://example.org/feed.atom");
+                let Feed::Atom(atom) = received.feed else {
+                    panic!("pushed an atom feed");
+                };
+                assert_eq!(atom.entry.len(), 1);
+                assert_eq!(atom.entry[0].title, "Pushed Entry");
+
+                let forged = signature.replace("sha256=", "sha256=00");
+                let resp = push(&forged).send().await.unwrap();
+                assert_eq!(resp.status(), StatusCode::ACCEPTED);
+                assert!(pushed.try_recv().is_err());
+
+                let resp = hub.post(callback(addr, "unknown")).send().await.unwrap();
+                assert_eq!(resp.status(), StatusCode::GONE);
+
+                let resp = hub
+                    .get(callback(addr, &sub.id))
+                    .query(&[
+                        ("hub.mode", "denied"),
+                        ("hub.topic", sub.topic.as_str()),
+                        ("hub.reason", "banned"),
+                    ])
+                    .send()
+                    .await
+                    .unwrap();
+                assert_eq!(resp.status(), StatusCode::OK);
+                let denied: Subscription = feeds.read().await[0].websub().cloned().unwrap();
+                assert!(!denied.active(Utc::now()));
+                assert!(denied.retry_at.is_some_and(|at| at > Utc::now()));
+            });
+    }
+}
@@ -12,11 +12,18 @@ mod opml;
 mod signal;
 mod update;
 
+#[cfg(not(test))]
 lazy_static! {
     static ref CONFIG: RwLock<config::Config> =
         RwLock::new(config::Config::new().unwrap_or_else(|e| panic!("{}", e)));
 }
 
+// Tests have no command line, token or config file
+#[cfg(test)]
+lazy_static! {
+    static ref CONFIG: RwLock<config::Config> = RwLock::new(config::Config::default());
+}
+
 // Read the configuration, parse variables, and start discord client
 #[tokio::main(flavor = "multi_thread")]
 async fn main() -> anyhow::Result<()> {
@@ -1,8 +1,7 @@
 use chrono::{DateTime, Utc};
-
 use serde::{Deserialize, Serialize};
 
-use crate::feed::Feed;
+use crate::feed::{public_url, Feed};
 
 #[derive(Serialize, Deserialize, Clone, Debug, Hash, PartialEq, Eq, Default)]
 pub struct Opml {
@@ -55,7 +54,7 @@ impl From<&Feed> for Outline {
                 content_type: "rss".into(),
                 title: Some(rss.channel.title.clone()),
                 description: Some(rss.channel.description.clone()),
-                xml_url: rss.channel.url.clone(),
+                xml_url: public_url(&rss.channel.url),
                 html_url: None,
             },
             Feed::Atom(atom) => {
@@ -68,10 +67,18 @@ impl From<&Feed> for Outline {
                     content_type: "atom".into(),
                     title: Some(atom.title.clone()),
                     description: atom.subtitle.clone(),
-                    xml_url: atom.url.clone(),
+                    xml_url: public_url(&atom.url),
                     html_url: link.map(|link| link.href.clone()),
                 }
             }
+            Feed::Json(json) => Self {
+                text: json.title.clone(),
+                content_type: "json".into(),
+                title: Some(json.title.clone()),
+                description: json.description.clone(),
+                xml_url: public_url(&json.url),
+                html_url: json.home_page_url.clone(),
+            },
         }
     }
 }
@@ -107,6 +114,7 @@ where
 }
 
 use crate::feed::atom::{AtomFeed, Link};
+use crate::feed::json::JsonFeed;
 use crate::feed::rss::{RssChannel, RssFeed};
 
 // Does not pull from URL
@@ -130,6 +138,13 @@ impl From<Outline> for Feed {
                     ..Default::default()
                 })
             }
+            "json" => Self::Json(JsonFeed {
+                title: outline.title.clone().unwrap_or_default(),
+                description: outline.description.clone(),
+                home_page_url: outline.html_url.clone(),
+                url: outline.xml_url.clone(),
+                ..Default::default()
+            }),
             _ => Self::Rss(RssFeed {
                 channel: RssChannel {
                     title: outline.title.clone().unwrap_or_default(),
@@ -1,4 +1,5 @@
 use quick_xml::{de, se};
+use reqwest::Url;
 use serenity::{model::prelude::*, prelude::*};
 use std::{
     collections::HashSet,
@@ -7,16 +8,19 @@ use std::{
 use tokio::{
     runtime::Handle,
     sync::{mpsc, Barrier},
-    task::JoinSet,
+    task::{JoinHandle, JoinSet},
     time::{sleep_until, Duration, Instant},
 };
 use tracing::{debug, error, info, info_span, instrument, warn, Instrument};
 
 use crate::discord;
-use crate::feed::{self, Feed};
+use crate::feed::{self, fetch, schedule, websub, Feed, Poll};
 use crate::opml::Opml;
 use crate::CONFIG;
 
+// Shortest sleep of the background loop between looking for due feeds
+const MIN_WAKE: Duration = Duration::from_secs(10);
+
 pub static COMMANDS: OnceLock<mpsc::Sender<(Command, Arc<Barrier>)>> = OnceLock::new();
 
 #[derive(Debug, Clone)]
@@ -25,6 +29,9 @@ pub enum Command {
     EditFeed(Message, String, EditArgs),
     RemoveFeed(Message, String),
     ReloadFeed(Message, Option<String>),
+    ResumeFeed(Message, String),
+    Status(Message, Option<String>),
+    SetAuth(Message, String, AuthArgs),
     MarkRead(String, String),   // Channel name, item url
     MarkUnread(String, String), // Channel name, item url
     Export(Message, Option<String>),
@@ -37,6 +44,76 @@ pub struct EditArgs {
     pub category: Option<String>,
     pub title: Option<String>,
     pub url: Option<String>,
+    // Seconds, Some(None) goes back to the default
+    pub interval: Option<Option<u64>>,
+    pub min_interval: Option<Option<u64>>,
+    pub max_interval: Option<Option<u64>>,
+    // Some(None) goes back to the configured mode
+    pub adaptive: Option<Option<bool>>,
+}
+
+// Change to the request options of a feed, made with ~auth
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthArgs {
+    Basic(String, Option<fetch::Secret>),
+    Bearer(fetch::Secret),
+    // Removes the header without a value
+    Header(String, Option<fetch::Secret>),
+    UserAgent(Option<String>),
+    Proxy(Option<fetch::Proxy>),
+    Tls(fetch::FeedTls),
+    // Seconds, None goes back to the default
+    Timeout(Option<u64>),
+    Clear,
+}
+
+impl AuthArgs {
+    fn apply(self, options: &mut fetch::RequestOptions) {
+        match self {
+            Self::Basic(username, password) => {
+                options.auth = Some(fetch::Auth::Basic { username, password })
+            }
+            Self::Bearer(token) => options.auth = Some(fetch::Auth::Bearer { token }),
+            Self::Header(name, value) => {
+                options
+                    .headers
+                    .retain(|(header, _)| !header.eq_ignore_ascii_case(&name));
+                if let Some(value) = value {
+                    options.headers.push((name, value));
+                }
+            }
+            Self::UserAgent(user_agent) => options.user_agent = user_agent,
+            Self::Proxy(proxy) => options.proxy = proxy,
+            Self::Tls(tls) => options.tls = tls,
+            Self::Timeout(timeout) => options.timeout = timeout,
+            Self::Clear => *options = Default::default(),
+        }
+    }
+
+    // What changed, without the secret
+    fn describe(&self) -> String {
+        match self {
+            Self::Basic(username, _) => format!("Set basic auth as {}", username),
+            Self::Bearer(_) => "Set bearer token".to_owned(),
+            Self::Header(name, Some(_)) => format!("Set header {}", name),
+            Self::Header(name, None) => format!("Removed header {}", name),
+            Self::UserAgent(Some(_)) => "Set user agent".to_owned(),
+            Self::UserAgent(None) => "Removed user agent".to_owned(),
+            Self::Proxy(Some(_)) => "Set proxy".to_owned(),
+            Self::Proxy(None) => "Removed proxy".to_owned(),
+            Self::Tls(fetch::FeedTls { insecure: true, .. }) => {
+                "**Disabled TLS certificate verification**, anyone between the bot and the host can read and change the feed,".to_owned()
+            }
+            Self::Tls(fetch::FeedTls {
+                pinned_certificate: Some(path),
+                ..
+            }) => format!("Pinned the certificate in {}", path.display()),
+            Self::Tls(_) => "Restored TLS certificate verification".to_owned(),
+            Self::Timeout(Some(timeout)) => format!("Set timeout to {} seconds", timeout),
+            Self::Timeout(None) => "Restored the default timeout".to_owned(),
+            Self::Clear => "Cleared request options".to_owned(),
+        }
+    }
 }
 
 // Run in background, configuring server and updating feeds, etc
@@ -49,21 +126,30 @@ pub async fn background_task(feeds: Vec<Feed>, ctx: Context) -> anyhow::Result<(
         .set(sender)
         .map_err(|_| anyhow::anyhow!("error setting COMMANDS"))?;
 
-    let interval = match CONFIG.read() {
-        Err(_) => 600, // default to 10 minutes
-        Ok(cfg) => cfg.interval,
-    };
-    let interval = Duration::from_secs(interval);
-    let mut to_sleep = Instant::now()
-        .checked_add(interval)
-        .expect("couldn't add interval to instant");
-
     let (spawned_sender, mut spawned_channel) = mpsc::channel(8);
+    // Updates run beside the loop so commands are still handled meanwhile
+    let mut updating = None;
+
+    let websub = CONFIG.read().ok().and_then(|cfg| cfg.websub.clone());
+    let (push_sender, mut pushes) = mpsc::channel(8);
+    if let Some(ref websub) = websub {
+        match websub::serve(websub.listen, feeds.clone(), push_sender) {
+            Err(e) => error!(
+                "Failed to start WebSub callback server, only polling: {}",
+                e
+            ),
+            Ok((_, server)) => drop(Handle::current().spawn(async move {
+                if let Err(e) = server.await {
+                    error!("WebSub callback server failed: {}", e);
+                }
+            })),
+        }
+    }
 
     debug!("Starting background loop");
     'L: loop {
         let recv = commands.recv();
-        let timer = sleep_until(to_sleep);
+        let timer = sleep_until(next_wake(&feeds, websub.is_some()).await);
 
         tokio::select! {
             cmdwait = recv => {
@@ -83,6 +169,13 @@ pub async fn background_task(feeds: Vec<Feed>, ctx: Context) -> anyhow::Result<(
                     },
                 }
             },
+            Some(push) = pushes.recv() => {
+                let feeds = feeds.clone();
+                let ctx = ctx.clone();
+                Handle::current().spawn(async move {
+                    apply_push(push, &feeds, &ctx).await;
+                });
+            },
             processed = spawned_channel.recv() => if let Some(Some(())) = processed {
                 if let Err(e) = exit_feeds_loop(feeds.read().await.as_ref()).await {
                     error!("Error exiting background_task: {}", e);
@@ -90,8 +183,19 @@ pub async fn background_task(feeds: Vec<Feed>, ctx: Context) -> anyhow::Result<(
                 break 'L;
             },
             _ = timer => {
-                update_feeds(feeds.write().await.as_mut(), false, &ctx).await;
-                to_sleep = Instant::now().checked_add(interval).expect("couldn't add interval to instant");
+                if updating.as_ref().is_some_and(|update: &JoinHandle<()>| !update.is_finished()) {
+                    debug!("Waiting for the previous update to finish.");
+                } else {
+                    let feeds = feeds.clone();
+                    let ctx = ctx.clone();
+                    let websub = websub.clone();
+                    updating = Some(Handle::current().spawn(async move {
+                        update_feeds(&feeds, None, false, &ctx).await;
+                        if let Some(websub) = websub {
+                            websub::renew(&feeds, &websub).await;
+                        }
+                    }));
+                }
             },
         }
     }
@@ -148,6 +252,24 @@ async fn add_feed(idx: usize, feeds: Arc<RwLock<Vec<Feed>>>, ctx: Context) {
                 });
             }
         }
+        Feed::Json(json) =>
+        {
+            #[allow(clippy::unnecessary_to_owned)]
+            for item in json.items.iter().cloned() {
+                let title = feed.title();
+                let ctx = ctx.clone();
+                let item_handle = async move {
+                    if let Err(e) = discord::publish_json_item(&title, &item, &ctx).await {
+                        warn!("Failed to publish json item to feed {title}: {e:?}");
+                    }
+                };
+                handles.spawn(async move {
+                    item_handle
+                        .instrument(info_span!("add_feed::feed_future::json_item"))
+                        .await;
+                });
+            }
+        }
     }
 
     while handles.join_next().await.is_some() {}
@@ -221,12 +343,54 @@ async fn process_command(cmd: Command, feeds: Arc<RwLock<Vec<Feed>>>, ctx: &Cont
             }
             let location = location.unwrap();
 
+            {
+                let mut guard = feeds.write().await;
+                let schedule = guard[location].schedule_mut();
+                if let Some(interval) = args.interval {
+                    info!("Setting feed {} interval to {:?}.", id, interval);
+                    schedule.interval = interval;
+                }
+                if let Some(min_interval) = args.min_interval {
+                    schedule.min_interval = min_interval;
+                }
+                if let Some(max_interval) = args.max_interval {
+                    schedule.max_interval = max_interval;
+                }
+                if let Some(adaptive) = args.adaptive {
+                    info!("Setting feed {} adaptive polling to {:?}.", id, adaptive);
+                    schedule.adaptive = adaptive;
+                }
+            }
+            // Only the schedule changed, the channel can stay as it is
+            if args.url.is_none() && args.category.is_none() && args.title.is_none() {
+                let interval = feeds.read().await[location].poll_interval(&schedule_defaults());
+                let reply = format!(
+                    "Feed {} is now polled every {} seconds.",
+                    id,
+                    interval.as_secs()
+                );
+                if let Err(e) = msg.reply(ctx, &reply).await {
+                    error!("Failed to send message to {}: {}", msg.channel_id.0, e);
+                }
+                if let Err(e) = feed::export(feeds.read().await.as_ref()).await {
+                    error!("Error writing feeds to file: {}", e);
+                }
+                return None;
+            }
+
+            let mut dropped = None;
             let push = {
                 let mut guard = feeds.write().await;
                 let feeds: &mut Vec<Feed> = guard.as_mut();
                 if let Some(url) = args.url {
                     info!("Setting feed {} url to {}.", id, url);
-                    feeds[location].set_url(&url);
+                    if feeds[location].move_to(&url) {
+                        warn!(
+                            "Dropped the credentials and headers of feed {} since it moved to another host.",
+                            url
+                        );
+                        dropped = Some(url);
+                    }
                 }
 
                 if let Some(category) = args.category {
@@ -257,6 +421,15 @@ async fn process_command(cmd: Command, feeds: Arc<RwLock<Vec<Feed>>>, ctx: &Cont
                 vec![feeds.remove(location)]
             };
 
+            if let Some(url) = dropped {
+                let reply = format!(
+                    "The credentials and headers of feed {} were dropped since they were for another host, use `~auth {}` to set them again if the new host needs them.",
+                    id, url
+                );
+                if let Err(e) = msg.reply(ctx, &reply).await {
+                    error!("Failed to send message to {}: {}", msg.channel_id.0, e);
+                }
+            }
             add_feeds(push, feeds, ctx).await;
             return None;
         }
@@ -272,37 +445,131 @@ async fn process_command(cmd: Command, feeds: Arc<RwLock<Vec<Feed>>>, ctx: &Cont
             info!("Reloading feed {:?}", id);
             if let Some(id) = id {
                 let channel_name = discord::title_to_channel_name(&id);
-                let location = feeds
-                    .read()
-                    .await
-                    .iter()
-                    .enumerate()
-                    .find_map(|(idx, feed)| {
-                        if discord::title_to_channel_name(feed.title()) == channel_name
-                            || feed.url() == id
-                        {
-                            Some(idx)
-                        } else {
-                            None
-                        }
-                    });
+                let url = feeds.read().await.iter().find_map(|feed| {
+                    if discord::title_to_channel_name(feed.title()) == channel_name
+                        || feed.url() == id
+                    {
+                        Some(feed.url())
+                    } else {
+                        None
+                    }
+                });
 
-                if location.is_none() {
+                if url.is_none() {
                     if let Err(e) = msg.reply(ctx, &format!("Feed {} not found", id)).await {
                         error!("Failed to send message to {}: {}", msg.channel_id.0, e);
                     }
                     warn!("Could not feed {} to edit.", id);
                     return None;
                 }
-                let location = location.unwrap();
 
-                let mut guard = feeds.write().await;
-                let feeds: &mut Vec<Feed> = guard.as_mut();
-                update_feeds(&mut feeds[location..=location], true, ctx).await;
+                update_feeds(&feeds, url.as_deref(), true, ctx).await;
             } else {
-                let mut guard = feeds.write().await;
-                let feeds: &mut Vec<Feed> = guard.as_mut();
-                update_feeds(feeds.as_mut_slice(), true, ctx).await;
+                update_feeds(&feeds, None, true, ctx).await;
+            }
+        }
+        Command::ResumeFeed(msg, id) => {
+            info!("Resuming feed {}", id);
+            let channel_name = discord::title_to_channel_name(&id);
+            let url = feeds.write().await.iter_mut().find_map(|feed| {
+                if discord::title_to_channel_name(feed.title()) == channel_name || feed.url() == id
+                {
+                    feed.health_mut().resume();
+                    Some(feed.url())
+                } else {
+                    None
+                }
+            });
+
+            let Some(url) = url else {
+                if let Err(e) = msg.reply(ctx, &format!("Feed {} not found", id)).await {
+                    error!("Failed to send message to {}: {}", msg.channel_id.0, e);
+                }
+                warn!("Could not find feed {} to resume.", id);
+                return None;
+            };
+
+            if let Err(e) = msg.reply(ctx, &format!("Resumed feed {}.", id)).await {
+                error!("Failed to send message to {}: {}", msg.channel_id.0, e);
+            }
+            update_feeds(&feeds, Some(&url), true, ctx).await;
+        }
+        Command::Status(msg, id) => {
+            info!("Showing status of {:?}", id);
+            let defaults = schedule_defaults();
+            let websub = CONFIG.read().is_ok_and(|cfg| cfg.websub.is_some());
+            let now = chrono::offset::Utc::now();
+            let channel_name = id.as_deref().map(discord::title_to_channel_name);
+            let lines: Vec<_> = feeds
+                .read()
+                .await
+                .iter()
+                .filter(|feed| match (&id, &channel_name) {
+                    (Some(id), Some(channel_name)) => {
+                        discord::title_to_channel_name(feed.title()) == *channel_name
+                            || feed.url() == *id
+                    }
+                    _ => true,
+                })
+                .map(|feed| feed_status(feed, &defaults, websub, now))
+                .collect();
+
+            let mut reply = String::new();
+            for (idx, line) in lines.iter().enumerate() {
+                // Keeps the reply under Discord's 2000 character limit
+                if reply.len() + line.len() > 1900 {
+                    reply.push_str(&format!("and {} more", lines.len() - idx));
+                    break;
+                }
+                reply.push_str(line);
+                reply.push('\n');
+            }
+            if reply.is_empty() {
+                reply = match id {
+                    Some(id) => format!("Feed {} not found", id),
+                    None => "No feeds.".to_string(),
+                };
+            }
+            if let Err(e) = msg.reply(ctx, &reply).await {
+                error!("Failed to send message to {}: {}", msg.channel_id.0, e);
+            }
+        }
+        Command::SetAuth(msg, id, args) => {
+            info!("Setting request options of feed {}", id);
+            let channel_name = discord::title_to_channel_name(&id);
+            let reply = args.describe();
+            let found = feeds.write().await.iter_mut().any(|feed| {
+                if discord::title_to_channel_name(feed.title()) == channel_name || feed.url() == id
+                {
+                    args.clone().apply(feed.request_options_mut());
+                    true
+                } else {
+                    false
+                }
+            });
+
+            // The command was deleted by ~auth since it may hold a secret
+            if !found {
+                if let Err(e) = msg
+                    .channel_id
+                    .say(ctx, &format!("Feed {} not found", id))
+                    .await
+                {
+                    error!("Failed to send message to {}: {}", msg.channel_id.0, e);
+                }
+                warn!("Could not find feed {} to set request options.", id);
+                return None;
+            }
+
+            if let Err(e) = msg
+                .channel_id
+                .say(ctx, &format!("{} for feed {}.", reply, id))
+                .await
+            {
+                error!("Failed to send message to {}: {}", msg.channel_id.0, e);
+            }
+            if let Err(e) = feed::export(feeds.read().await.as_ref()).await {
+                error!("Error writing feeds to file: {}", e);
             }
         }
         Command::MarkRead(name, link) => {
@@ -337,6 +604,16 @@ async fn process_command(cmd: Command, feeds: Arc<RwLock<Vec<Feed>>>, ctx: &Cont
                             error!("Could not find atom entry with link {}.", link);
                             None
                         }),
+                    Feed::Json(json) => json
+                        .items
+                        .iter()
+                        .enumerate()
+                        .find(|(_, item)| item.get_link_href() == link)
+                        .map(|(idx, _)| (feed_idx, idx))
+                        .or_else(|| {
+                            error!("Could not find json item with link {}.", link);
+                            None
+                        }),
                 },
             };
 
@@ -347,6 +624,7 @@ async fn process_command(cmd: Command, feeds: Arc<RwLock<Vec<Feed>>>, ctx: &Cont
                     match &mut feeds[feed_idx] {
                         Feed::Rss(ref mut rss) => rss.channel.item[idx].read = Some(()),
                         Feed::Atom(ref mut atom) => atom.entry[idx].read = Some(()),
+                        Feed::Json(ref mut json) => json.items[idx].read = Some(()),
                     }
                 }
 
@@ -388,6 +666,16 @@ async fn process_command(cmd: Command, feeds: Arc<RwLock<Vec<Feed>>>, ctx: &Cont
                             error!("Could not find atom entry with link {}.", link);
                             None
                         }),
+                    Feed::Json(json) => json
+                        .items
+                        .iter()
+                        .enumerate()
+                        .find(|(_, item)| item.get_link_href() == link)
+                        .map(|(idx, _)| (feed_idx, idx))
+                        .or_else(|| {
+                            error!("Could not find json item with link {}.", link);
+                            None
+                        }),
                 },
             };
 
@@ -398,6 +686,7 @@ async fn process_command(cmd: Command, feeds: Arc<RwLock<Vec<Feed>>>, ctx: &Cont
                     match &mut feeds[feed_idx] {
                         Feed::Rss(ref mut rss) => rss.channel.item[idx].read = None,
                         Feed::Atom(ref mut atom) => atom.entry[idx].read = None,
+                        Feed::Json(ref mut json) => json.items[idx].read = None,
                     }
                 }
 
@@ -448,10 +737,16 @@ async fn process_command(cmd: Command, feeds: Arc<RwLock<Vec<Feed>>>, ctx: &Cont
                     );
                     return None;
                 }
-                Ok(v) => String::from_utf8_lossy(v.as_slice()).to_string(),
+                Ok(v) => {
+                    let charset = attachment
+                        .content_type
+                        .as_deref()
+                        .and_then(feed::charset::from_content_type);
+                    feed::charset::to_utf8(&v, charset.as_deref())
+                }
             };
 
-            let opml: Opml = match de::from_str(&opml) {
+            let opml: Opml = match de::from_reader(opml.as_slice()) {
                 Err(e) => {
                     if let Err(e) = msg
                         .reply(ctx, &format!("Could not parse opml file: {}.", e))
@@ -478,13 +773,9 @@ async fn process_command(cmd: Command, feeds: Arc<RwLock<Vec<Feed>>>, ctx: &Cont
                         return false;
                     }
 
-                    let user_agent = CONFIG
-                        .read()
-                        .expect("failed to get CONFIG static")
-                        .user_agent
-                        .clone();
-
-                    match feed::from_url(feed.url(), Some(feed.title()), None, user_agent).await {
+                    match feed::from_url(feed.url(), Some(feed.title()), None, &Default::default())
+                        .await
+                    {
                         Err(e) => {
                             warn!("Could not load feed from url {}: {}.", feed.url(), e);
                         }
@@ -512,105 +803,224 @@ async fn process_command(cmd: Command, feeds: Arc<RwLock<Vec<Feed>>>, ctx: &Cont
     None
 }
 
-#[instrument(skip(ctx))]
-async fn diff_feed(update: Feed, feeds: &mut [Feed], ctx: &Context) {
+// Items an update added to a feed, published once the feeds are unlocked
+#[derive(Debug)]
+enum NewItems {
+    Rss(Vec<feed::rss::RssItem>),
+    Atom(Vec<feed::atom::Entry>),
+    Json(Vec<feed::json::Item>),
+}
+
+// Store what's new in update with the feed of the same url, returning the
+// title of the feed and the items to publish. Nothing is sent to Discord, so
+// the feeds can stay locked while this runs. With items_only the feed's own
+// fields are kept, for pushed content that may only carry the new items.
+#[instrument(skip(feeds))]
+fn diff_feed(update: Feed, feeds: &mut [Feed], items_only: bool) -> Option<(String, NewItems)> {
     let feed = feeds.iter_mut().find(|f| f.url() == update.url());
     if let Some(mut feed) = feed {
         info!("Updating feed {}.", feed.title());
-        match (update, &mut feed) {
+        let known_items = feed.item_count();
+        let new_items = match (update, &mut feed) {
             (Feed::Rss(update), Feed::Rss(ref mut rss)) => {
                 debug!("Feed {} is RSS.", rss.channel.title);
                 debug!("Updating feed {} items.", rss.channel.title);
                 let mut set = HashSet::with_capacity(rss.channel.item.len());
                 set.extend(rss.channel.item.iter().map(|i| i.link.clone()));
+                let mut new_items = Vec::new();
                 for item in update.channel.item {
                     if !set.contains(&item.link) {
                         info!("Feed {} new item: {:?}.", rss.channel.title, item.title);
-                        if let Err(e) =
-                            discord::publish_rss_item(&rss.channel.title, &item, ctx).await
-                        {
-                            warn!(
-                                "Error publishing rss item {} ({:?}) to discord: {}",
-                                item.link, item.title, e
-                            );
-                        }
-                        rss.channel.item.push(item);
+                        rss.channel.item.push(item.clone());
+                        new_items.push(item);
                     }
                 }
 
-                debug!("Updating feed {} metadata.", rss.channel.title);
-                rss.channel.description = update.channel.description;
-                rss.channel.copyright = update.channel.copyright;
-                rss.channel.managing_editor = update.channel.managing_editor;
-                rss.channel.web_master = update.channel.web_master;
-                rss.channel.pub_date = update.channel.pub_date;
-                rss.channel.category = update.channel.category;
-                rss.channel.docs = update.channel.docs;
-                rss.channel.ttl = update.channel.ttl;
-                rss.channel.image = update.channel.image;
-                rss.channel.skip_hours = update.channel.skip_hours;
-                rss.channel.skip_days = update.channel.skip_days;
-                rss.channel.last_updated = Some(chrono::offset::Utc::now());
+                if !items_only {
+                    debug!("Updating feed {} metadata.", rss.channel.title);
+                    rss.channel.description = update.channel.description;
+                    rss.channel.copyright = update.channel.copyright;
+                    rss.channel.managing_editor = update.channel.managing_editor;
+                    rss.channel.web_master = update.channel.web_master;
+                    rss.channel.pub_date = update.channel.pub_date;
+                    rss.channel.category = update.channel.category;
+                    rss.channel.docs = update.channel.docs;
+                    rss.channel.ttl = update.channel.ttl;
+                    rss.channel.sy_update_period = update.channel.sy_update_period;
+                    rss.channel.sy_update_frequency = update.channel.sy_update_frequency;
+                    rss.channel.sy_update_base = update.channel.sy_update_base;
+                    rss.channel.image = update.channel.image;
+                    rss.channel.skip_hours = update.channel.skip_hours;
+                    rss.channel.skip_days = update.channel.skip_days;
+                    rss.channel.diagnostics = update.channel.diagnostics;
+                    rss.channel.validators = update.channel.validators;
+                    rss.channel.last_updated = Some(chrono::offset::Utc::now());
+                }
+                NewItems::Rss(new_items)
             }
             (Feed::Atom(update), Feed::Atom(ref mut atom)) => {
                 debug!("Feed {} is atom.", atom.title);
                 debug!("Updating feed {} items.", atom.title);
                 let mut set = HashSet::with_capacity(atom.entry.len());
                 set.extend(atom.entry.iter().map(|e| e.id.clone()));
+                let mut new_entries = Vec::new();
                 for entry in update.entry {
                     if !set.contains(&entry.id) {
                         info!("Feed {} hew item: {}.", atom.title, entry.title);
-                        if let Err(e) = discord::publish_atom_entry(&atom.title, &entry, ctx).await
-                        {
-                            warn!(
-                                "Error publishing atem item {} to discord: {}",
-                                entry.title, e
-                            );
-                        } else {
-                            atom.entry.push(entry);
-                        }
+                        atom.entry.push(entry.clone());
+                        new_entries.push(entry);
                     }
                 }
 
-                debug!("Updating feed {} metadata", atom.title);
-                atom.id = update.id;
-                atom.updated = update.updated;
-                atom.author = update.author;
-                atom.link = update.link;
-                atom.category = update.category;
-                atom.icon = update.icon;
-                atom.logo = update.logo;
-                atom.rights = update.rights;
-                atom.subtitle = update.subtitle;
-                atom.ttl = update.ttl;
-                atom.skip_days = update.skip_days;
-                atom.skip_hours = update.skip_hours;
-                atom.last_updated = Some(chrono::offset::Utc::now());
-            }
-            _ => error!("Mismatched feed type between update and current feed",),
+                if !items_only {
+                    debug!("Updating feed {} metadata", atom.title);
+                    atom.id = update.id;
+                    atom.updated = update.updated;
+                    atom.author = update.author;
+                    atom.link = update.link;
+                    atom.category = update.category;
+                    atom.icon = update.icon;
+                    atom.logo = update.logo;
+                    atom.rights = update.rights;
+                    atom.subtitle = update.subtitle;
+                    atom.ttl = update.ttl;
+                    atom.sy_update_period = update.sy_update_period;
+                    atom.sy_update_frequency = update.sy_update_frequency;
+                    atom.sy_update_base = update.sy_update_base;
+                    atom.skip_days = update.skip_days;
+                    atom.skip_hours = update.skip_hours;
+                    atom.diagnostics = update.diagnostics;
+                    atom.validators = update.validators;
+                    atom.last_updated = Some(chrono::offset::Utc::now());
+                }
+                NewItems::Atom(new_entries)
+            }
+            (Feed::Json(update), Feed::Json(ref mut json)) => {
+                debug!("Feed {} is json.", json.title);
+                debug!("Updating feed {} items.", json.title);
+                let mut set = HashSet::with_capacity(json.items.len());
+                set.extend(json.items.iter().map(|i| i.id.clone()));
+                let mut new_items = Vec::new();
+                for item in update.items {
+                    if !set.contains(&item.id) {
+                        info!("Feed {} new item: {:?}.", json.title, item.title);
+                        json.items.push(item.clone());
+                        new_items.push(item);
+                    }
+                }
+
+                if !items_only {
+                    debug!("Updating feed {} metadata", json.title);
+                    json.version = update.version;
+                    json.home_page_url = update.home_page_url;
+                    json.feed_url = update.feed_url;
+                    json.description = update.description;
+                    json.icon = update.icon;
+                    json.favicon = update.favicon;
+                    json.authors = update.authors;
+                    json.author = update.author;
+                    json.language = update.language;
+                    json.expired = update.expired;
+                    json.hubs = update.hubs;
+                    json.diagnostics = update.diagnostics;
+                    json.validators = update.validators;
+                    json.last_updated = Some(chrono::offset::Utc::now());
+                }
+                NewItems::Json(new_items)
+            }
+            _ => {
+                error!("Mismatched feed type between update and current feed",);
+                return None;
+            }
+        };
+        let count = feed.item_count().saturating_sub(known_items);
+        feed.learn_schedule(count, chrono::offset::Utc::now());
+        return Some((feed.title(), new_items));
+    }
+    None
+}
+
+// Send the items diff_feed found to the feed's channel. Atom entries and JSON
+// items that fail to publish are taken out of the feed again, so the next
+// update tries them again.
+#[instrument(skip(new_items, feeds, ctx))]
+async fn publish_new_items(
+    url: &str,
+    title: &str,
+    new_items: NewItems,
+    feeds: &RwLock<Vec<Feed>>,
+    ctx: &Context,
+) {
+    let mut failed = HashSet::new();
+    match new_items {
+        NewItems::Rss(items) => {
+            for item in items {
+                if let Err(e) = discord::publish_rss_item(title, &item, ctx).await {
+                    warn!(
+                        "Error publishing rss item {} ({:?}) to discord: {}",
+                        item.link, item.title, e
+                    );
+                }
+            }
+        }
+        NewItems::Atom(entries) => {
+            for entry in entries {
+                if let Err(e) = discord::publish_atom_entry(title, &entry, ctx).await {
+                    warn!(
+                        "Error publishing atem item {} to discord: {}",
+                        entry.title, e
+                    );
+                    failed.insert(entry.id);
+                }
+            }
+        }
+        NewItems::Json(items) => {
+            for item in items {
+                if let Err(e) = discord::publish_json_item(title, &item, ctx).await {
+                    warn!(
+                        "Error publishing json item {} ({:?}) to discord: {}",
+                        item.id, item.title, e
+                    );
+                    failed.insert(item.id);
+                }
+            }
         }
-        info!(
-            "Finised sending updates to discord for feed {}.",
-            feed.title()
-        );
+    }
+    info!("Finised sending updates to discord for feed {}.", title);
+
+    if failed.is_empty() {
+        return;
+    }
+    let mut guard = feeds.write().await;
+    match guard.iter_mut().find(|f| f.url() == url) {
+        Some(Feed::Atom(ref mut atom)) => atom.entry.retain(|e| !failed.contains(&e.id)),
+        Some(Feed::Json(ref mut json)) => json.items.retain(|i| !failed.contains(&i.id)),
+        _ => (),
     }
 }
 
-#[instrument(skip(ctx))]
-async fn update_feeds(feeds: &mut [Feed], force: bool, ctx: &Context) {
+// Poll the feeds due for an update, or all of them when forced, or only the
+// feed at the url. The lock is only held to apply the results, so a slow
+// server doesn't keep commands from using the feeds.
+#[instrument(skip(feeds, ctx))]
+async fn update_feeds(feeds: &RwLock<Vec<Feed>>, only: Option<&str>, force: bool, ctx: &Context) {
     info!("Updating feeds");
+    let now = chrono::offset::Utc::now();
+    let defaults = schedule_defaults();
+    let websub = CONFIG.read().is_ok_and(|cfg| cfg.websub.is_some());
     let mut futures = JoinSet::new();
-    for feed in feeds.iter_mut() {
-        if force || feed.should_update() {
+    for feed in feeds.read().await.iter() {
+        if should_poll(feed, only, force, websub, now, &defaults) {
             let url = feed.url();
-            futures.spawn(async {
+            let options = fetch::Options {
+                validators: feed.validators().clone(),
+                request: feed.request_options().clone(),
+            };
+            let rejected_url = feed.health().rejected_url.clone();
+            futures.spawn(async move {
                 info!("Updating feed at {}.", url);
-                let user_agent = CONFIG
-                    .read()
-                    .expect("failed to get CONFIG static")
-                    .user_agent
-                    .clone();
-                feed::from_url(url, None, None, user_agent).await
+                let polled = poll_feed(&url, &options, rejected_url).await;
+                (url, polled)
             });
         }
     }
@@ -618,16 +1028,353 @@ async fn update_feeds(feeds: &mut [Feed], force: bool, ctx: &Context) {
     while let Some(res) = futures.join_next().await {
         match res {
             Err(e) => error!("Error joining update feed task: {}", e),
-            Ok(Err(e)) => error!("Error updating feed: {}", e),
-            Ok(Ok(update)) => diff_feed(update, feeds, ctx).await,
+            Ok((url, Err(e))) => {
+                error!("Error updating feed {}: {}", url, e);
+                record_failure(&url, e, feeds, ctx).await;
+            }
+            Ok((
+                url,
+                Ok(Polled {
+                    poll: Poll::Modified(mut update),
+                    moved,
+                    rejected_url,
+                }),
+            )) => {
+                // Discord is only told once the feeds are unlocked again
+                let (notice, new_items, url) = {
+                    let mut guard = feeds.write().await;
+                    let mut notice = None;
+                    if let Some(reason) = moved {
+                        notice = move_feed(&url, &update.url(), reason, guard.as_mut());
+                        if notice.is_none() {
+                            update.set_url(url);
+                        }
+                    }
+                    let url = update.url();
+                    if let Some(feed) = guard.iter_mut().find(|f| f.url() == url && websub) {
+                        websub::track_hub(feed, &update);
+                    }
+                    let new_items = diff_feed(*update, guard.as_mut(), false);
+                    if let Some(feed) = guard.iter_mut().find(|f| f.url() == url) {
+                        let health = feed.health_mut();
+                        health.rejected_url = rejected_url;
+                        health.record_success(chrono::offset::Utc::now());
+                    }
+                    (notice, new_items, url)
+                };
+                if let Some((title, notice)) = notice {
+                    if let Err(e) = discord::publish_notice(&title, &notice, ctx).await {
+                        warn!("Failed to tell feed {} it moved: {}", title, e);
+                    }
+                }
+                if let Some((title, new_items)) = new_items {
+                    publish_new_items(&url, &title, new_items, feeds, ctx).await;
+                }
+            }
+            Ok((
+                url,
+                Ok(Polled {
+                    poll: Poll::NotModified(validators),
+                    ..
+                }),
+            )) => {
+                let mut guard = feeds.write().await;
+                if let Some(feed) = guard.iter_mut().find(|f| f.url() == url) {
+                    info!("Feed {} has not changed.", feed.title());
+                    feed.record_not_modified(validators, chrono::offset::Utc::now());
+                }
+            }
         };
     }
 
-    if let Err(e) = feed::export(feeds).await {
+    if let Err(e) = feed::export(feeds.read().await.as_ref()).await {
         error!("Error writing feeds to file: {}", e);
     }
 }
 
+// Whether update_feeds polls the feed: it's the one asked for, or any feed
+// when only is None, it isn't paused or backing off after failures, and it's
+// due or the poll is forced
+fn should_poll(
+    feed: &Feed,
+    only: Option<&str>,
+    force: bool,
+    websub: bool,
+    now: chrono::DateTime<chrono::Utc>,
+    defaults: &schedule::Defaults,
+) -> bool {
+    if only.is_some_and(|url| feed.url() != url) {
+        return false;
+    }
+    if !feed.health().may_poll(force, now) {
+        debug!("Feed {} is paused or backing off.", feed.title());
+        return false;
+    }
+    if !force && websub && is_pushed(feed, now) {
+        debug!("Feed {} is pushed by its hub.", feed.title());
+        return false;
+    }
+    force
+        || feed
+            .next_poll(now, defaults)
+            .is_some_and(|next| next <= now)
+}
+
+// The configured schedule of feeds without their own
+fn schedule_defaults() -> schedule::Defaults {
+    match CONFIG.read() {
+        Err(_) => schedule::Defaults::default(),
+        Ok(cfg) => schedule::Defaults {
+            interval: Duration::from_secs(cfg.interval),
+            adaptive: cfg.adaptive,
+            min_interval: Duration::from_secs(cfg.min_interval),
+            max_interval: Duration::from_secs(cfg.max_interval),
+        },
+    }
+}
+
+// How the feed is polled and when it is next, for ~status
+fn feed_status(
+    feed: &Feed,
+    defaults: &schedule::Defaults,
+    websub: bool,
+    now: chrono::DateTime<chrono::Utc>,
+) -> String {
+    let mut status = format!(
+        "{}: every {} ({})",
+        feed.title(),
+        schedule::format_interval(feed.poll_interval(defaults)),
+        feed.schedule().describe(defaults, feed.ttl())
+    );
+    if websub && is_pushed(feed, now) {
+        status.push_str(", pushed by its hub");
+    }
+    let health = feed.health();
+    match feed.next_poll(now, defaults) {
+        None if health.paused => status.push_str(", paused"),
+        None => status.push_str(", expired"),
+        Some(next) if next <= now => status.push_str(", due now"),
+        Some(next) => status.push_str(&format!(
+            ", next poll in {}",
+            schedule::format_interval((next - now).to_std().unwrap_or_default())
+        )),
+    }
+    if health.failures > 0 {
+        status.push_str(&format!(", {} failed polls in a row", health.failures));
+    }
+    status
+}
+
+// When the next feed is due, at least MIN_WAKE from now so feeds that stay due
+// while an update runs don't spin the loop, and at most the configured
+// interval from now so changes to it apply
+async fn next_wake(feeds: &RwLock<Vec<Feed>>, websub: bool) -> Instant {
+    let defaults = schedule_defaults();
+    let interval = defaults.interval;
+    let now = chrono::offset::Utc::now();
+    let soonest = feeds
+        .read()
+        .await
+        .iter()
+        .filter_map(|feed| {
+            if websub && is_pushed(feed, now) {
+                let pushed = chrono::Duration::from_std(websub::PUSHED_POLL_INTERVAL).ok()?;
+                return Some(feed.last_updated()? + pushed);
+            }
+            feed.next_poll(now, &defaults)
+        })
+        .min();
+
+    let wait = soonest
+        .map_or(interval, |due| (due - now).to_std().unwrap_or_default())
+        .min(interval)
+        .max(MIN_WAKE);
+    Instant::now() + wait
+}
+
+// Whether the feed's hub pushes its updates, so it's only polled once in a
+// while in case the hub stopped without saying so
+fn is_pushed(feed: &Feed, now: chrono::DateTime<chrono::Utc>) -> bool {
+    feed.websub().is_some_and(|sub| sub.active(now))
+        && feed.last_updated().is_some_and(|last| {
+            (now - last).to_std().unwrap_or_default() < websub::PUSHED_POLL_INTERVAL
+        })
+}
+
+// Apply content a hub pushed like a poll of the feed
+#[instrument(skip(feeds, ctx))]
+async fn apply_push(push: websub::Push, feeds: &RwLock<Vec<Feed>>, ctx: &Context) {
+    info!("Received pushed update for feed {}.", push.url);
+    let new_items = {
+        let mut guard = feeds.write().await;
+        let mut update = push.feed;
+        update.set_url(&push.url);
+        // Hubs may push only what changed, the rest is left to polls
+        diff_feed(update, guard.as_mut(), true)
+    };
+    if let Some((title, new_items)) = new_items {
+        publish_new_items(&push.url, &title, new_items, feeds, ctx).await;
+    }
+    if let Err(e) = feed::export(feeds.read().await.as_ref()).await {
+        error!("Error writing feeds to file: {}", e);
+    }
+}
+
+// A poll and where the feed moved to, if it did
+struct Polled {
+    poll: Poll,
+    // Why the update is for another url than the one polled
+    moved: Option<String>,
+    rejected_url: Option<String>,
+}
+
+// Poll the feed, then the canonical url it names if it names one that isn't
+// known not to work. The feed only moves there when the canonical url serves
+// the same kind of feed and names itself as canonical, so two urls naming each
+// other don't make the feed move back and forth.
+#[instrument(skip(options))]
+async fn poll_feed(
+    url: &str,
+    options: &fetch::Options,
+    mut rejected_url: Option<String>,
+) -> anyhow::Result<Polled> {
+    let update = match feed::poll(url, options).await? {
+        Poll::Modified(update) => update,
+        poll => {
+            return Ok(Polled {
+                poll,
+                moved: None,
+                rejected_url,
+            })
+        }
+    };
+    let mut moved = (update.url() != url).then(|| "the server permanently redirects it".to_owned());
+
+    let Some(canonical) = update.canonical_url() else {
+        return Ok(Polled {
+            poll: Poll::Modified(update),
+            moved,
+            rejected_url: None,
+        });
+    };
+    if rejected_url.as_ref() == Some(&canonical) {
+        return Ok(Polled {
+            poll: Poll::Modified(update),
+            moved,
+            rejected_url,
+        });
+    }
+
+    info!("Feed at {} names {} as its address.", url, canonical);
+    let options = match (Url::parse(url), Url::parse(&canonical)) {
+        (Ok(from), Ok(to)) => options.elsewhere(&from, &to),
+        _ => Default::default(),
+    };
+    match feed::poll(&canonical, &options).await {
+        Ok(Poll::Modified(feed))
+            if std::mem::discriminant(feed.as_ref()) == std::mem::discriminant(update.as_ref())
+                && feed.canonical_url().is_none() =>
+        {
+            moved = Some(format!("it names {} as its address", canonical));
+            rejected_url = None;
+            return Ok(Polled {
+                poll: Poll::Modified(feed),
+                moved,
+                rejected_url,
+            });
+        }
+        Ok(_) => warn!(
+            "{} doesn't serve the same feed as {}, ignoring it.",
+            canonical, url
+        ),
+        Err(e) => warn!(
+            "Could not load {} named by {}, ignoring it: {}",
+            canonical, url, e
+        ),
+    }
+    rejected_url = Some(canonical);
+    Ok(Polled {
+        poll: Poll::Modified(update),
+        moved,
+        rejected_url,
+    })
+}
+
+// Store the feed under the url it moved to, returning its title and the
+// notice for its channel. Returns None when another feed already uses that
+// url, leaving the feed where it was.
+#[instrument(skip(feeds))]
+fn move_feed(from: &str, to: &str, reason: String, feeds: &mut [Feed]) -> Option<(String, String)> {
+    if feeds.iter().any(|f| f.url() == to) {
+        warn!("Feed {} moved to {}, which is already a feed.", from, to);
+        return None;
+    }
+    let feed = feeds.iter_mut().find(|f| f.url() == from)?;
+
+    info!("Feed {} moved to {} because {}.", from, to, reason);
+    let mut notice = format!(
+        "This feed moved from {} to {} because {}.",
+        from, to, reason
+    );
+    if feed.move_to(to) {
+        warn!(
+            "Dropped the credentials and headers of feed {} since it moved to another host.",
+            to
+        );
+        notice.push_str(&format!(
+            "\nIts credentials and headers were dropped since they were for another host, use `~auth {}` to set them again if the new host needs them.",
+            to
+        ));
+    }
+    Some((feed.title(), notice))
+}
+
+// Track a failed poll, pausing the feed and telling its channel once it has
+// failed max_failures times in a row
+#[instrument(skip(feeds, ctx))]
+async fn record_failure(url: &str, err: anyhow::Error, feeds: &RwLock<Vec<Feed>>, ctx: &Context) {
+    let status = err.downcast_ref::<fetch::StatusError>();
+    // A feed that is gone won't come back by retrying
+    let gone = status.is_some_and(|e| e.status == reqwest::StatusCode::GONE);
+    let max_failures = match CONFIG.read() {
+        _ if gone => 1,
+        Err(_) => 10,
+        Ok(cfg) => cfg.max_failures,
+    };
+    let retry_after = status.and_then(|e| e.retry_after);
+
+    let paused = {
+        let mut guard = feeds.write().await;
+        let Some(feed) = guard.iter_mut().find(|f| f.url() == url) else {
+            return;
+        };
+        let health = feed.health_mut();
+        health
+            .record_failure(&err, retry_after, max_failures, chrono::offset::Utc::now())
+            .then(|| (feed.title(), feed.health().failures))
+    };
+
+    if let Some((title, failures)) = paused {
+        warn!("Pausing feed {} after {} failed polls.", title, failures);
+        let notice = if gone {
+            format!(
+                "Paused this feed because the server says it is gone for good.\nUse `~remove {}` to remove it, or `~resume {}` if it comes back.",
+                url, url
+            )
+        } else {
+            format!(
+            "Paused this feed after {} failed updates, the last one failed with: {}\nUse `~resume {}` once it is fixed.",
+            failures,
+            discord::truncate(&err.to_string(), 1500),
+            url
+        )
+        };
+        if let Err(e) = discord::publish_notice(&title, &notice, ctx).await {
+            warn!("Failed to tell feed {} it was paused: {}", title, e);
+        }
+    }
+}
+
 #[instrument]
 async fn exit_feeds_loop(feeds: &[Feed]) -> anyhow::Result<()> {
     debug!("Exiting the background loop");
@@ -636,3 +1383,120 @@ async fn exit_feeds_loop(feeds: &[Feed]) -> anyhow::Result<()> {
         .await
         .map_err(|e| anyhow::anyhow!("could not save feeds data: {}", e))
 }
+
+#[cfg(test)]
+mod test {
+    use chrono::{TimeZone, Utc};
+
+    use std::{fs::File, io::BufReader, path::PathBuf};
+
+    use super::{diff_feed, should_poll, NewItems};
+    use crate::feed::{rss, Feed};
+
+    fn get_test_dir() -> PathBuf {
+        std::env::current_dir()
+            .expect("failed to get current directory")
+            .join("test")
+    }
+
+    #[test]
+    fn new_items() {
+        let file = File::open(get_test_dir().join("rssboard.xml")).unwrap();
+        let mut update = Feed::Rss(rss::xml_from_reader("rssboard", BufReader::new(file)).unwrap());
+        update.set_url("https://example.com/feed.xml");
+        let mut stored = update.clone();
+        if let Feed::Rss(ref mut rss) = stored {
+            rss.channel.item.truncate(3);
+        }
+        let mut feeds = vec![stored];
+
+        let Some((title, NewItems::Rss(items))) = diff_feed(update.clone(), &mut feeds, false)
+        else {
+            panic!("expected new rss items");
+        };
+        assert_eq!(title, update.title());
+        assert_eq!(items.len(), update.item_count() - 3);
+        assert_eq!(feeds[0].item_count(), update.item_count());
+
+        let Some((_, NewItems::Rss(items))) = diff_feed(update.clone(), &mut feeds, false) else {
+            panic!("expected rss items");
+        };
+        assert!(items.is_empty());
+
+        update.set_url("https://example.com/other.xml");
+        assert!(diff_feed(update, &mut feeds, false).is_none());
+    }
+
+    #[test]
+    fn pushed_items() {
+        let file = File::open(get_test_dir().join("rssboard.xml")).unwrap();
+        let mut stored = Feed::Rss(rss::xml_from_reader("rssboard", BufReader::new(file)).unwrap());
+        stored.set_url("https://example.com/feed.xml");
+        let mut push = stored.clone();
+        if let Feed::Rss(ref mut rss) = stored {
+            rss.channel.item.truncate(3);
+            rss.channel.ttl = Some(60);
+        }
+        // Carries the items but not all of the channel
+        if let Feed::Rss(ref mut rss) = push {
+            rss.channel.description.clear();
+            rss.channel.ttl = None;
+        }
+        let mut feeds = vec![stored];
+
+        let Some((_, NewItems::Rss(items))) = diff_feed(push.clone(), &mut feeds, true) else {
+            panic!("expected new rss items");
+        };
+        assert_eq!(items.len(), push.item_count() - 3);
+        let Feed::Rss(ref rss) = feeds[0] else {
+            panic!("stored an rss feed");
+        };
+        assert_eq!(rss.channel.ttl, Some(60));
+        assert!(!rss.channel.description.is_empty());
+    }
+
+    #[test]
+    fn failing_feeds() {
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+        let defaults = Default::default();
+        let url = "https://example.com/feed.xml";
+        let mut feed = Feed::default();
+        feed.set_url(url);
+        let polled =
+            |feed: &Feed, only, force, now| should_poll(feed, only, force, false, now, &defaults);
+        assert!(polled(&feed, None, false, now));
+        assert!(polled(&feed, Some(url), false, now));
+        assert!(!polled(
+            &feed,
+            Some("https://example.com/other.xml"),
+            true,
+            now
+        ));
+
+        // Backing off until retry_at, unless forced
+        let health = feed.health_mut();
+        assert!(!health.record_failure("503", None, 3, now));
+        let retry_at = health.retry_at.unwrap();
+        assert!(retry_at > now);
+        assert!(!polled(&feed, None, false, now));
+        assert!(!polled(
+            &feed,
+            None,
+            false,
+            retry_at - chrono::Duration::seconds(1)
+        ));
+        assert!(polled(&feed, None, false, retry_at));
+        assert!(polled(&feed, Some(url), true, now));
+
+        // Paused feeds aren't polled at all until resumed
+        let health = feed.health_mut();
+        health.record_failure("503", None, 3, now);
+        assert!(health.record_failure("503", None, 3, now));
+        let later = now + chrono::Duration::days(7);
+        assert!(!polled(&feed, None, false, later));
+        assert!(!polled(&feed, Some(url), true, later));
+
+        feed.health_mut().resume();
+        assert!(polled(&feed, None, false, now));
+    }
+}